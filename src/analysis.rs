@@ -0,0 +1,238 @@
+use crate::{bar::Bar, key::Key};
+
+/// The Krumhansl-Kessler key profiles, starting at the tonic.
+const MAJOR: [f64; 12] = [
+	6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR: [f64; 12] = [
+	6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// How many bars on either side of a bar are weighed to find its key.
+const WINDOW: usize = 4;
+/// The fewest bars a key has to last to count as a modulation.
+const MIN_BARS: usize = 4;
+
+/// The keys a song seems to be in, judging by the notes played.
+#[derive(Clone, Default)]
+pub struct Analysis {
+	/// The key of the whole song.
+	pub key: Option<Key>,
+	/// The bars the key changes at, and the new keys; starting with the
+	/// opening key at the first bar with notes.
+	pub changes: Vec<(usize, Key)>,
+}
+
+fn correlation(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+	let mean = |x: &[f64; 12]| x.iter().sum::<f64>() / 12.0;
+	let (ma, mb) = (mean(a), mean(b));
+	let (mut cov, mut va, mut vb) = (0.0, 0.0, 0.0);
+	for i in 0..12 {
+		let (da, db) = (a[i] - ma, b[i] - mb);
+		cov += da * db;
+		va += da * da;
+		vb += db * db;
+	}
+	if va == 0.0 || vb == 0.0 {
+		0.0
+	} else {
+		cov / (va * vb).sqrt()
+	}
+}
+
+/// Finds the key whose profile matches the pitch class durations in `pcs`
+/// best.
+fn estimate(pcs: &[f64; 12]) -> Option<Key> {
+	if pcs.iter().all(|&n| n == 0.0) {
+		return None;
+	}
+
+	let mut best = (f64::MIN, Key::from_tonic(0, false));
+	for tonic in 0..12 {
+		// Line the durations up with the profiles, starting at the tonic.
+		let mut rotated = [0.0; 12];
+		for (i, n) in rotated.iter_mut().enumerate() {
+			*n = pcs[(tonic + i) % 12];
+		}
+		for (profile, minor) in [(&MAJOR, false), (&MINOR, true)] {
+			let r = correlation(&rotated, profile);
+			if r > best.0 {
+				best = (r, Key::from_tonic(tonic as u8, minor));
+			}
+		}
+	}
+	Some(best.1)
+}
+
+fn add(a: &mut [f64; 12], b: &[f64; 12]) {
+	for (x, y) in a.iter_mut().zip(b) {
+		*x += y;
+	}
+}
+
+impl Analysis {
+	pub fn new(bars: &[Bar]) -> Self {
+		let pcs = bars.iter().map(|b| b.pitch_classes()).collect::<Vec<_>>();
+
+		let mut total = [0.0; 12];
+		for p in &pcs {
+			add(&mut total, p);
+		}
+
+		// The key around every bar.
+		let local = (0..pcs.len()).map(|i| {
+			let mut sum = [0.0; 12];
+			for p in &pcs[i.saturating_sub(WINDOW)..(i + WINDOW + 1).min(pcs.len())] {
+				add(&mut sum, p);
+			}
+			estimate(&sum)
+		});
+
+		// Runs of bars in the same key.
+		let mut runs = Vec::<(usize, usize, Key)>::new();
+		for (i, key) in local.enumerate() {
+			match (runs.last_mut(), key) {
+				(Some((_, len, k)), Some(key)) if *k == key => *len += 1,
+				(Some((_, len, _)), None) => *len += 1,
+				(_, Some(key)) => runs.push((i, 1, key)),
+				(None, None) => (),
+			}
+		}
+
+		// Short detours are more likely borrowed chords than modulations, fold
+		// them into the key before them.
+		let mut changes = Vec::<(usize, usize, Key)>::new();
+		for (start, len, key) in runs {
+			match changes.last_mut() {
+				Some((_, prev_len, prev)) if *prev == key || len < MIN_BARS => *prev_len += len,
+				_ => changes.push((start, len, key)),
+			}
+		}
+		// Likewise, a short opening is taken to be in the key that follows.
+		if changes.len() > 1 && changes[0].1 < MIN_BARS {
+			let (start, len, _) = changes.remove(0);
+			changes[0].0 = start;
+			changes[0].1 += len;
+		}
+
+		Self {
+			key: estimate(&total),
+			changes: changes
+				.into_iter()
+				.map(|(start, _, key)| (start, key))
+				.collect(),
+		}
+	}
+
+	/// Returns the key the `n`th bar seems to be in.
+	pub fn key_at(&self, n: usize) -> Option<Key> {
+		self.changes
+			.iter()
+			.rev()
+			.find(|(start, _)| *start <= n)
+			.or_else(|| self.changes.first())
+			.map(|(_, key)| *key)
+	}
+
+	/// Returns `self` with every key transposed by `shift` semitones.
+	pub fn transpose(&self, shift: i8) -> Self {
+		Self {
+			key: self.key.map(|k| k.transpose(shift)),
+			changes: self
+				.changes
+				.iter()
+				.map(|&(n, k)| (n, k.transpose(shift)))
+				.collect(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use midly::{num::u4, MidiMessage};
+	use nodi::{Event, MidiEvent, Moment, Sheet};
+
+	use super::*;
+	use crate::bar;
+
+	/// The tonic, subdominant and dominant chords of the major key on `tonic`,
+	/// arpeggiated a bar each and ending on the tonic.
+	fn cadence(tonic: u8) -> Vec<[u8; 4]> {
+		[
+			[0, 4, 7, 12],
+			[5, 9, 12, 17],
+			[7, 11, 14, 19],
+			[0, 4, 7, 12],
+		]
+		.iter()
+		.map(|bar| bar.map(|n| 60 + tonic + n))
+		.collect()
+	}
+
+	/// Plays every bar as four quarter notes, at four ticks a beat.
+	fn bars(notes: &[[u8; 4]]) -> Vec<Bar> {
+		let midi = |key: u8, vel: u8| {
+			Event::Midi(MidiEvent {
+				channel: u4::new(0),
+				message: MidiMessage::NoteOn {
+					key: key.into(),
+					vel: vel.into(),
+				},
+			})
+		};
+		let mut moments = vec![Moment::Empty; notes.len() * 16 + 1];
+		for (i, &key) in notes.iter().flatten().enumerate() {
+			moments[i * 4].push(midi(key, 100));
+			moments[i * 4 + 4].push(midi(key, 0));
+		}
+		bar::bars(moments.into_iter().collect::<Sheet>(), 4)
+	}
+
+	fn key(s: &str) -> Key {
+		s.parse().unwrap()
+	}
+
+	#[test]
+	fn estimates_the_key_of_a_scale() {
+		let bars = bars(&[[60, 62, 64, 65], [67, 69, 71, 72]]);
+		assert_eq!(Analysis::new(&bars).key, Some(key("C major")));
+
+		let mut minor = [0.0; 12];
+		for pc in [9, 11, 0, 2, 4, 5, 8] {
+			minor[pc] = 1.0;
+		}
+		minor[9] = 2.0;
+		assert_eq!(estimate(&minor), Some(key("A minor")));
+		assert_eq!(estimate(&[0.0; 12]), None);
+	}
+
+	#[test]
+	fn reports_long_passages_in_another_key_as_modulations() {
+		let mut notes = cadence(0);
+		notes.extend(cadence(0));
+		notes.extend(cadence(4));
+		notes.extend(cadence(4));
+		let a = Analysis::new(&bars(&notes));
+
+		assert_eq!(a.changes.len(), 2);
+		assert_eq!(a.changes[0], (0, key("C major")));
+		let (start, second) = a.changes[1];
+		assert_eq!(second, key("E major"));
+		assert!((5..=10).contains(&start));
+		assert_eq!(a.key_at(0), Some(key("C major")));
+		assert_eq!(a.key_at(15), Some(key("E major")));
+	}
+
+	#[test]
+	fn folds_short_detours_into_the_key_before() {
+		let mut notes = cadence(0);
+		notes.extend(cadence(0));
+		notes.extend(&cadence(4)[..MIN_BARS - 1]);
+		notes.extend(cadence(0));
+		notes.extend(cadence(0));
+		let a = Analysis::new(&bars(&notes));
+
+		assert_eq!(a.changes, [(0, key("C major"))]);
+		assert_eq!(a.key_at(9), Some(key("C major")));
+	}
+}
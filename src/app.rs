@@ -1,5 +1,7 @@
 use clap::{arg, crate_version, Arg, Command};
 
+use crate::Target;
+
 const FOOTER: &str =
 	"For the configuration file syntax, visit https://github.com/insomnimus/midnote";
 
@@ -12,6 +14,7 @@ pub fn new() -> Command<'static> {
 			arg!(-c --config [PATH] "Path to a config file (*.json)."),
 			arg!(-C --"no-color" "Do not use colored output."),
 			arg!(-l --list "List available MIDI output devices."),
+			arg!(-n --"no-resume" "Start from the beginning instead of where the file was left off."),
 			arg!(-d --device <NO> "The MIDI output device.")
 				.default_value("0")
 				.validator(|s| {
@@ -19,6 +22,16 @@ pub fn new() -> Command<'static> {
 						String::from("the value must be an integer greater than or equal to 0")
 					})
 				}),
+			arg!(--"bar-length" [SECONDS] "The length of a 4/4 bar, for files timed in SMPTE frames.")
+				.validator(|s| {
+					s.parse::<f64>()
+						.ok()
+						.filter(|&n| n > 0.0)
+						.map(|_| {})
+						.ok_or_else(|| String::from("the value must be a positive number"))
+				}),
+			arg!(-t --transpose [TARGET] "Transpose to a key (such as 'G major') or for an instrument (such as 'alto sax').")
+				.validator(|s| s.parse::<Target>().map(|_| {})),
 			Arg::new("file")
 				.help("The midi file to inspect.")
 				.required_unless_present("list"),
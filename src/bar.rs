@@ -1,55 +1,809 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
-use nodi::{timers::Ticker, Event, Moment, Sheet, Timer};
+use midly::{
+	num::{u4, u7},
+	MidiMessage, PitchBend,
+};
+use nodi::{timers::Ticker, Event, MidiEvent, Moment, Sheet, Timer};
+
+use crate::{
+	drum::moment_drums,
+	key::Key,
+	moment_notes,
+	rhythm::{Beat, Length, TimeSignature},
+	Element, Note, Notes, TimedNote,
+};
 
 pub struct Bar {
 	pub timer: Ticker,
+	pub tpb: u16,
+	pub time_sig: TimeSignature,
+	/// The key signature in effect, if the file declares one.
+	pub key: Option<Key>,
+	/// The key the notes around this bar suggest, if there are any.
+	pub estimated_key: Option<Key>,
 	pub moments: Vec<Moment>,
+	/// How long each note is held in ticks, keyed by the offset of its NoteOn in
+	/// this bar, its channel and its key.
+	lengths: HashMap<(usize, u8, u8), u32>,
+	/// How many ticks into this bar the notes from previous bars keep sounding.
+	held: usize,
+	/// The programs, controllers and pitch bends set before this bar, to send
+	/// before playing it.
+	pub chase: Vec<MidiEvent>,
+	/// The notes struck in previous bars that are still sounding at the start
+	/// of this one.
+	ties: Vec<Tie>,
+	/// The highest note struck last before this bar, drums aside.
+	prev_pitch: Option<u8>,
+}
+
+/// A note held over from a previous bar.
+#[derive(Copy, Clone)]
+struct Tie {
+	channel: u8,
+	key: u8,
+	vel: u8,
+	/// How many ticks it keeps sounding into the bar.
+	len: u32,
+}
+
+const RESET_ALL_CONTROLLERS: u8 = 121;
+/// The controllers Reset All Controllers leaves alone, with their General MIDI
+/// defaults: bank select, volume, pan, reverb and chorus.
+const UNRESET_DEFAULTS: [(u8, u8); 6] = [(0, 0), (32, 0), (7, 100), (10, 64), (91, 40), (93, 0)];
+/// The NRPN and RPN selects, most significant byte first.
+const PARAMETER_SELECT: [u8; 4] = [99, 98, 101, 100];
+
+/// The settings of every channel, as set by the events so far.
+struct Channels {
+	programs: [Option<u7>; 16],
+	/// Controllers 0 to 119; the rest are channel mode messages.
+	controllers: [[Option<u7>; 120]; 16],
+	bends: [Option<PitchBend>; 16],
+}
+
+impl Channels {
+	fn new() -> Self {
+		Self {
+			programs: [None; 16],
+			controllers: [[None; 120]; 16],
+			bends: [None; 16],
+		}
+	}
+
+	fn update(&mut self, ch: u8, msg: MidiMessage) {
+		let ch = ch as usize;
+		match msg {
+			MidiMessage::ProgramChange { program } => self.programs[ch] = Some(program),
+			MidiMessage::Controller { controller, value } if controller < 120 => {
+				self.controllers[ch][controller.as_int() as usize] = Some(value);
+			}
+			MidiMessage::Controller { controller, .. } if controller == RESET_ALL_CONTROLLERS => {
+				for (n, cc) in self.controllers[ch].iter_mut().enumerate() {
+					if !UNRESET_DEFAULTS.iter().any(|&(kept, _)| kept as usize == n) {
+						*cc = None;
+					}
+				}
+				self.bends[ch] = None;
+			}
+			MidiMessage::PitchBend { bend } => self.bends[ch] = Some(bend),
+			_ => (),
+		}
+	}
+
+	/// Returns the events that restore these settings.
+	///
+	/// Every channel is reset first, and the settings not made yet are sent with
+	/// their General MIDI defaults, so that settings changed later in the song
+	/// do not linger.
+	fn events(&self) -> Vec<MidiEvent> {
+		let mut buf = Vec::new();
+		for ch in 0..16 {
+			let channel = u4::new(ch as u8);
+			let mut push = |message| buf.push(MidiEvent { channel, message });
+			let ccs = &self.controllers[ch];
+			let controller = |n: u8| {
+				let default = UNRESET_DEFAULTS
+					.iter()
+					.find(|&&(cc, _)| cc == n)
+					.map(|&(_, value)| value.into());
+				ccs[n as usize]
+					.or(default)
+					.map(|value| MidiMessage::Controller {
+						controller: n.into(),
+						value,
+					})
+			};
+
+			push(MidiMessage::Controller {
+				controller: RESET_ALL_CONTROLLERS.into(),
+				value: 0.into(),
+			});
+			// The bank has to be selected before the program change.
+			for n in [0, 32] {
+				push(controller(n).unwrap());
+			}
+			push(MidiMessage::ProgramChange {
+				program: self.programs[ch].unwrap_or_else(|| 0.into()),
+			});
+			// Likewise, the parameter has to be selected before data entry.
+			for n in PARAMETER_SELECT {
+				if let Some(msg) = controller(n) {
+					push(msg);
+				}
+			}
+			for n in 0..120 {
+				match n {
+					0 | 32 | 98..=101 => (),
+					_ => {
+						if let Some(msg) = controller(n) {
+							push(msg);
+						}
+					}
+				}
+			}
+			if let Some(bend) = self.bends[ch] {
+				push(MidiMessage::PitchBend { bend });
+			}
+		}
+		buf
+	}
 }
 
-pub fn bars(sheet: Sheet, tpb: u16) -> Vec<Bar> {
+/// Pairs every NoteOn in `sheet` with its NoteOff, returning the lengths keyed
+/// by the absolute tick, channel and key of the NoteOn.
+///
+/// Like synths do, striking a key that is already sounding on the same channel
+/// ends the note before it.
+fn note_lengths(sheet: &Sheet) -> HashMap<(usize, u8, u8), u32> {
+	let mut open = HashMap::<(u8, u8), usize>::new();
+	let mut lengths = HashMap::new();
+
+	for (tick, m) in sheet.iter().enumerate() {
+		let events = match m {
+			Moment::Empty => continue,
+			Moment::Events(events) => events,
+		};
+
+		let midi = || {
+			events.iter().filter_map(|e| match e {
+				Event::Midi(m) => Some((m.channel.as_int(), m.message)),
+				_ => None,
+			})
+		};
+
+		// Handle releases first so a note struck again on the same tick
+		// does not close itself.
+		for (ch, msg) in midi() {
+			let released = match msg {
+				MidiMessage::NoteOff { key, .. } => key,
+				MidiMessage::NoteOn { key, vel } if vel == 0 => key,
+				_ => continue,
+			};
+			if let Some(start) = open.remove(&(ch, released.as_int())) {
+				lengths.insert((start, ch, released.as_int()), (tick - start) as u32);
+			}
+		}
+
+		for (ch, msg) in midi() {
+			if let MidiMessage::NoteOn { key, vel } = msg {
+				if vel > 0 {
+					let k = (ch, key.as_int());
+					match open.get(&k) {
+						// The same note twice on one tick is struck once.
+						Some(&start) if start == tick => (),
+						Some(&start) => {
+							lengths.insert((start, ch, k.1), (tick - start) as u32);
+							open.insert(k, tick);
+						}
+						None => {
+							open.insert(k, tick);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	lengths
+}
+
+fn time_signature(m: &Moment) -> Option<TimeSignature> {
+	match m {
+		Moment::Empty => None,
+		Moment::Events(events) => events.iter().find_map(|e| match e {
+			Event::TimeSignature(numerator, denominator, ..) => Some(TimeSignature {
+				numerator: *numerator,
+				denominator: *denominator,
+			}),
+			_ => None,
+		}),
+	}
+}
+
+fn key_signature(m: &Moment) -> Option<Key> {
+	match m {
+		Moment::Empty => None,
+		Moment::Events(events) => events.iter().find_map(|e| match e {
+			Event::KeySignature(sharps, minor) => Some(Key {
+				sharps: *sharps,
+				minor: *minor,
+			}),
+			_ => None,
+		}),
+	}
+}
+
+pub fn bars(mut sheet: Sheet, tpb: u16) -> Vec<Bar> {
+	// nodi falls back to 4/16 when there is no time signature, use 4/4 instead.
+	if !sheet.is_empty() && time_signature(&sheet[0]).is_none() {
+		sheet[0].push(Event::TimeSignature(4, 2, 24, 8));
+	}
+
+	let mut lengths = note_lengths(&sheet);
 	let mut timer = Ticker::new(tpb);
+	let mut time_sig = TimeSignature::default();
+	let mut key = None;
 	let mut buf = Vec::new();
+	let mut start = 0_usize;
+	// The tick every note struck so far is released by.
+	let mut sounding_until = 0_usize;
+	// The notes that may last into later bars, with the tick they end at.
+	let mut open = Vec::<(Tie, usize)>::new();
+	let mut channels = Channels::new();
+	let mut prev_pitch = None;
+
 	for bar in sheet.into_bars(tpb) {
 		let t = timer;
-		// check if we have a tempo event
-		for m in &bar {
+		// Like nodi, a time signature takes effect on the next bar unless it is
+		// at the very start of this one; nodi ends the bar at one found later.
+		// Key signatures are handled the same way.
+		if let Some(ts) = bar.first().and_then(time_signature) {
+			time_sig = ts;
+		}
+		if let Some(k) = bar.first().and_then(key_signature) {
+			key = Some(k);
+		}
+		let bar_time_sig = time_sig;
+		let bar_key = key;
+		if let Some(ts) = bar.iter().skip(1).rev().find_map(time_signature) {
+			time_sig = ts;
+		}
+		if let Some(k) = bar.iter().skip(1).rev().find_map(key_signature) {
+			key = Some(k);
+		}
+		let held = sounding_until.saturating_sub(start).min(bar.len());
+		let chase = channels.events();
+		open.retain(|(_, end)| *end > start);
+		let ties = open
+			.iter()
+			.map(|&(tie, end)| Tie {
+				len: (end - start) as u32,
+				..tie
+			})
+			.collect::<Vec<_>>();
+		let bar_prev_pitch = prev_pitch;
+		let mut bar_lengths = HashMap::new();
+		// check if we have a tempo event and collect the note lengths
+		for (i, m) in bar.iter().enumerate() {
 			match &m {
 				Moment::Empty => (),
 				Moment::Events(events) => {
+					let mut top = None;
 					for e in events {
-						if let Event::Tempo(n) = e {
-							timer.change_tempo(*n);
+						match e {
+							Event::Tempo(n) => timer.change_tempo(*n),
+							Event::Midi(m) => match m.message {
+								msg @ (MidiMessage::ProgramChange { .. }
+								| MidiMessage::Controller { .. }
+								| MidiMessage::PitchBend { .. }) => channels.update(m.channel.as_int(), msg),
+								MidiMessage::NoteOn { key, vel } if vel > 0 => {
+									let (ch, key) = (m.channel.as_int(), key.as_int());
+									let len = lengths.remove(&(start + i, ch, key));
+									if ch != 9 {
+										top = top.max(Some(key));
+										// A note that is never released is only taken to
+										// sound to the end of its own bar.
+										let end = len
+											.map_or(start + bar.len(), |n| start + i + n as usize);
+										sounding_until = sounding_until.max(end);
+									}
+									if let Some(len) = len {
+										bar_lengths.insert((i, ch, key), len);
+										if ch != 9 && i + len as usize > bar.len() {
+											let tie = Tie {
+												channel: ch,
+												key,
+												vel: vel.as_int(),
+												len,
+											};
+											open.push((tie, start + i + len as usize));
+										}
+									}
+								}
+								_ => (),
+							},
+							_ => (),
 						}
 					}
+					prev_pitch = top.or(prev_pitch);
 				}
 			}
 		}
+
+		start += bar.len();
 		buf.push(Bar {
 			timer: t,
+			tpb,
+			time_sig: bar_time_sig,
+			key: bar_key,
+			estimated_key: None,
 			moments: bar,
+			lengths: bar_lengths,
+			held,
+			chase,
+			ties,
+			prev_pitch: bar_prev_pitch,
 		});
 	}
 	buf
 }
 
+/// Transposes `moments` in place.
+fn transpose_vec(mut moments: Vec<Moment>, n: i8) -> Vec<Moment> {
+	if n != 0 {
+		for m in &mut moments {
+			m.transpose(n, false);
+		}
+	}
+	moments
+}
+
+fn transpose(moments: &[Moment], n: i8) -> Cow<'_, [Moment]> {
+	if n == 0 {
+		moments.into()
+	} else {
+		let mut moments = moments.to_vec();
+		for m in &mut moments {
+			m.transpose(n, false);
+		}
+		moments.into()
+	}
+}
+
+/// Removes the empty moments at the end of `moments`, and at the start too if
+/// `start` is set.
+fn trim(moments: &[Moment], start: bool) -> &[Moment] {
+	let start = if start {
+		moments.iter().take_while(|m| m.is_empty()).count()
+	} else {
+		0
+	};
+	let slice = &moments[start..];
+	let end = slice.iter().rev().take_while(|m| m.is_empty()).count();
+	&slice[..(slice.len() - end)]
+}
+
 impl<'a> Bar {
-	pub fn trim_moments(&self) -> &[Moment] {
-		let start = self.moments.iter().take_while(|m| m.is_empty()).count();
-		let slice = &self.moments[start..];
-		let end = slice.iter().rev().take_while(|m| m.is_empty()).count();
-		&slice[..(slice.len() - end)]
+	/// Returns the moments of this bar with the notes held over from the
+	/// previous bar struck again at the start.
+	fn with_ties(&self) -> Vec<Moment> {
+		let mut moments = self.moments.clone();
+		let last = moments.len().saturating_sub(1);
+		for t in &self.ties {
+			let channel = u4::new(t.channel);
+			let (key, vel) = (t.key.into(), t.vel.into());
+			moments[0].push(Event::Midi(MidiEvent {
+				channel,
+				message: MidiMessage::NoteOn { key, vel },
+			}));
+			// The release is in a later bar, do not leave the note hanging.
+			if t.len as usize > last {
+				moments[last].push(Event::Midi(MidiEvent {
+					channel,
+					message: MidiMessage::NoteOff { key, vel: 0.into() },
+				}));
+			}
+		}
+		moments
 	}
 
+	/// Returns the moments of this bar for playing it on its own, without the
+	/// silence around them.
+	///
+	/// Notes held over from the previous bar are struck again, so the start of
+	/// the bar is kept if there are any.
 	pub fn transposed_moments(&'a self, n: i8) -> Cow<'a, [Moment]> {
-		if n == 0 {
-			self.trim_moments().into()
+		if self.ties.is_empty() {
+			transpose(trim(&self.moments, true), n)
+		} else {
+			let mut moments = self.with_ties();
+			moments.truncate(trim(&moments, false).len());
+			transpose_vec(moments, n).into()
+		}
+	}
+
+	/// Like [Self::transposed_moments] but keeps the empty moments at the
+	/// start and the end of the bar. Held over notes are struck again only with
+	/// `ties`.
+	pub fn transposed_bar(&'a self, n: i8, ties: bool) -> Cow<'a, [Moment]> {
+		if ties && !self.ties.is_empty() {
+			transpose_vec(self.with_ties(), n).into()
 		} else {
-			let mut moments = self.trim_moments().to_vec();
-			for m in &mut moments {
-				m.transpose(n, false);
+			transpose(&self.moments, n)
+		}
+	}
+
+	/// Returns the notes in this bar along with their lengths, and the rests
+	/// between them.
+	pub fn notes(&self, shift: i8) -> Notes {
+		let mut buf = Notes::default();
+		let key = self.key.or(self.estimated_key).map(|k| k.transpose(shift));
+		// The offset nothing is sounding from.
+		let mut free = self.held;
+
+		let tied = self
+			.ties
+			.iter()
+			.filter_map(|t| {
+				let n = t.key as i32 + shift as i32;
+				if !(0..=127).contains(&n) {
+					return None;
+				}
+				let note = Note::new(n as u8, key);
+				let len = t.len.min(self.moments.len() as u32);
+				Some(TimedNote {
+					note,
+					length: Length::approximate(len, self.tpb),
+					from: None,
+				})
+			})
+			.fold(Vec::<TimedNote>::new(), |mut buf, n| {
+				if !buf.iter().any(|x| x.note == n.note) {
+					buf.push(n);
+				}
+				buf
+			});
+		if !tied.is_empty() {
+			buf.elements.push((self.beat(0), Element::Tied(tied)));
+		}
+		buf.out_of_range = self.out_of_range(shift);
+
+		for (i, m) in self.moments.iter().enumerate() {
+			let before = free;
+			let notes = moment_notes(m, shift, key, |ch, k| match self.lengths.get(&(i, ch, k)) {
+				Some(&len) => {
+					free = free.max(i + len as usize);
+					Length::approximate(len, self.tpb)
+				}
+				None => {
+					free = usize::MAX;
+					Vec::new()
+				}
+			});
+
+			if let Some(notes) = notes {
+				self.push_rest(&mut buf, before, i);
+				buf.elements.push((self.beat(i), Element::Notes(notes)));
 			}
-			moments.into()
 		}
+
+		self.push_rest(&mut buf, free, self.moments.len());
+		self.set_intervals(&mut buf.elements, shift);
+		buf
+	}
+
+	/// Points every note struck in this bar at the highest note struck before
+	/// it, for telling the intervals of the melody.
+	fn set_intervals(&self, elements: &mut [(Beat, Element)], shift: i8) {
+		let mut prev = self
+			.prev_pitch
+			.map(|n| n as i32 + shift as i32)
+			.filter(|n| (0..=127).contains(n))
+			.map(|n| n as u8);
+		for (_, e) in elements {
+			if let Element::Notes(notes) = e {
+				for n in notes.iter_mut() {
+					n.from = prev;
+				}
+				prev = notes.iter().map(|n| n.note.pitch()).max().or(prev);
+			}
+		}
+	}
+
+	/// Returns how many beats every pitch class sounds for in this bar.
+	pub fn pitch_classes(&self) -> [f64; 12] {
+		let mut buf = [0.0; 12];
+		let beats = |ticks: u32| ticks as f64 / self.tpb as f64;
+		for (i, m) in self.moments.iter().enumerate() {
+			if let Moment::Events(events) = m {
+				for e in events {
+					match e {
+						Event::Midi(m) if m.channel != 9 => {
+							if let MidiMessage::NoteOn { key, vel } = m.message {
+								let (ch, key) = (m.channel.as_int(), key.as_int());
+								if vel > 0 {
+									// Notes never released are counted as a beat.
+									let len = self.lengths.get(&(i, ch, key)).copied();
+									buf[key as usize % 12] += len.map_or(1.0, beats);
+								}
+							}
+						}
+						_ => (),
+					}
+				}
+			}
+		}
+		for t in &self.ties {
+			buf[t.key as usize % 12] += beats(t.len.min(self.moments.len() as u32));
+		}
+		buf
+	}
+
+	/// Counts the notes that `shift` moves out of the MIDI range, which are not
+	/// played or shown.
+	fn out_of_range(&self, shift: i8) -> usize {
+		let out = |key: u8| !(0..=127).contains(&(key as i32 + shift as i32));
+		let struck = self
+			.moments
+			.iter()
+			.filter_map(|m| match m {
+				Moment::Events(events) => Some(events),
+				Moment::Empty => None,
+			})
+			.flatten()
+			.filter(|e| match e {
+				Event::Midi(m) if m.channel != 9 => matches!(
+					m.message,
+					MidiMessage::NoteOn { key, vel } if vel > 0 && out(key.as_int())
+				),
+				_ => false,
+			})
+			.count();
+		struck + self.ties.iter().filter(|t| out(t.key)).count()
+	}
+
+	/// Returns the drums hit in this bar.
+	///
+	/// Drum hits have no meaningful length, so no rests are reported.
+	pub fn drums(&self) -> Notes {
+		let mut buf = Notes::default();
+		for (i, m) in self.moments.iter().enumerate() {
+			if let Some(drums) = moment_drums(m) {
+				buf.elements.push((self.beat(i), Element::Drums(drums)));
+			}
+		}
+		buf
+	}
+
+	fn push_rest(&self, buf: &mut Notes, from: usize, to: usize) {
+		if from >= to {
+			return;
+		}
+		let rest = Length::split((to - from) as u32, self.tpb);
+		if !rest.is_empty() {
+			buf.elements.push((self.beat(from), Element::Rest(rest)));
+		}
+	}
+
+	fn beat(&self, offset: usize) -> Beat {
+		Beat::new(offset, self.tpb, self.time_sig)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn note(ch: u8, key: u8, vel: u8) -> Event {
+		Event::Midi(MidiEvent {
+			channel: u4::new(ch),
+			message: MidiMessage::NoteOn {
+				key: key.into(),
+				vel: vel.into(),
+			},
+		})
+	}
+
+	fn sheet(events: &[(usize, Event)]) -> Sheet {
+		let len = events.iter().map(|(t, _)| t + 1).max().unwrap_or(0);
+		let mut moments = vec![Moment::Empty; len];
+		for (tick, e) in events {
+			moments[*tick].push(*e);
+		}
+		moments.into_iter().collect()
+	}
+
+	#[test]
+	fn pairs_note_on_with_note_off() {
+		let lengths = note_lengths(&sheet(&[
+			(0, note(0, 60, 100)),
+			(0, note(1, 60, 100)),
+			(4, note(0, 60, 0)),
+			(6, note(1, 60, 0)),
+		]));
+		assert_eq!(lengths.len(), 2);
+		assert_eq!(lengths[&(0, 0, 60)], 4);
+		assert_eq!(lengths[&(0, 1, 60)], 6);
+	}
+
+	#[test]
+	fn striking_again_ends_the_note() {
+		let lengths = note_lengths(&sheet(&[
+			(0, note(0, 60, 100)),
+			(2, note(0, 60, 100)),
+			(4, note(0, 60, 0)),
+		]));
+		assert_eq!(lengths[&(0, 0, 60)], 2);
+		assert_eq!(lengths[&(2, 0, 60)], 2);
+	}
+
+	#[test]
+	fn released_and_struck_on_the_same_tick() {
+		let lengths = note_lengths(&sheet(&[
+			(0, note(0, 60, 100)),
+			(3, note(0, 60, 0)),
+			(3, note(0, 60, 100)),
+			(5, note(0, 60, 0)),
+		]));
+		assert_eq!(lengths[&(0, 0, 60)], 3);
+		assert_eq!(lengths[&(3, 0, 60)], 2);
+	}
+
+	#[test]
+	fn unreleased_notes_have_no_length() {
+		let lengths = note_lengths(&sheet(&[(0, note(0, 60, 100)), (4, note(0, 62, 0))]));
+		assert!(lengths.is_empty());
+	}
+
+	fn elements(bar: &Bar) -> Vec<String> {
+		bar.notes(0)
+			.elements
+			.iter()
+			.map(|(_, e)| e.to_string())
+			.collect()
+	}
+
+	#[test]
+	fn unreleased_notes_do_not_hold_later_bars() {
+		// Four ticks a beat, sixteen a bar.
+		let bars = bars(
+			sheet(&[
+				(0, note(0, 60, 100)),
+				(2, note(0, 60, 100)),
+				(4, note(0, 64, 100)),
+				(8, note(0, 64, 0)),
+				(48, note(0, 67, 100)),
+				(52, note(0, 67, 0)),
+			]),
+			4,
+		);
+		assert_eq!(bars.len(), 4);
+		assert_eq!(elements(&bars[1]), ["whole rest"]);
+		assert_eq!(elements(&bars[2]), ["whole rest"]);
+	}
+
+	fn time_sig(numerator: u8, denominator: u8) -> Event {
+		Event::TimeSignature(numerator, denominator, 24, 8)
+	}
+
+	#[test]
+	fn time_signatures_in_the_middle_of_a_bar_apply_from_the_next() {
+		let bars = bars(
+			sheet(&[
+				(0, note(0, 60, 100)),
+				(8, time_sig(3, 2)),
+				(30, note(0, 60, 0)),
+			]),
+			4,
+		);
+		let meters = bars
+			.iter()
+			.map(|b| b.time_sig.to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(meters, ["4/4", "3/4", "3/4"]);
+		assert_eq!(bars[1].moments.len(), 12);
+	}
+
+	#[test]
+	fn key_signatures_in_the_middle_of_a_bar_apply_from_the_next() {
+		let bars = bars(
+			sheet(&[
+				(0, Event::KeySignature(0, false)),
+				(5, Event::KeySignature(-3, true)),
+				(20, note(0, 60, 100)),
+			]),
+			4,
+		);
+		let keys = bars
+			.iter()
+			.map(|b| b.key.map(|k| k.to_string()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			keys,
+			[Some("C major".to_string()), Some("C minor".to_string())]
+		);
+	}
+
+	fn controllers(events: &[MidiEvent]) -> Vec<u8> {
+		events
+			.iter()
+			.filter_map(|e| match e.message {
+				MidiMessage::Controller { controller, .. } => Some(controller.as_int()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn chase_resets_then_selects_parameters_before_data_entry() {
+		let mut channels = Channels::new();
+		for (n, value) in [(101, 0), (100, 0), (6, 12), (7, 90), (1, 40)] {
+			channels.update(
+				0,
+				MidiMessage::Controller {
+					controller: n.into(),
+					value: value.into(),
+				},
+			);
+		}
+		let events = channels.events();
+		let first = events
+			.iter()
+			.filter(|e| e.channel == 0)
+			.copied()
+			.collect::<Vec<_>>();
+		assert_eq!(
+			controllers(&first),
+			[121, 0, 32, 101, 100, 1, 6, 7, 10, 91, 93]
+		);
+		// Untouched channels are reset too.
+		let other = events
+			.iter()
+			.filter(|e| e.channel == 1)
+			.copied()
+			.collect::<Vec<_>>();
+		assert_eq!(controllers(&other), [121, 0, 32, 7, 10, 91, 93]);
+	}
+
+	#[test]
+	fn reset_all_controllers_in_the_song_is_chased() {
+		let mut channels = Channels::new();
+		for n in [1, 7, 121] {
+			channels.update(
+				0,
+				MidiMessage::Controller {
+					controller: n.into(),
+					value: 100.into(),
+				},
+			);
+		}
+		let first = channels
+			.events()
+			.into_iter()
+			.filter(|e| e.channel == 0)
+			.collect::<Vec<_>>();
+		assert_eq!(controllers(&first), [121, 0, 32, 7, 10, 91, 93]);
+	}
+
+	fn programs(events: &[MidiEvent], ch: u8) -> Vec<u8> {
+		events
+			.iter()
+			.filter(|e| e.channel == ch)
+			.filter_map(|e| match e.message {
+				MidiMessage::ProgramChange { program } => Some(program.as_int()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn unset_programs_are_sent_as_the_default() {
+		let mut channels = Channels::new();
+		channels.update(3, MidiMessage::ProgramChange { program: 40.into() });
+		let events = channels.events();
+		assert_eq!(programs(&events, 3), [40]);
+		assert_eq!(programs(&events, 0), [0]);
 	}
 }
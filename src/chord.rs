@@ -0,0 +1,193 @@
+use std::{
+	fmt,
+	sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{
+	key::Key,
+	names,
+	note::{self, Note},
+};
+
+static VIEW: AtomicU8 = AtomicU8::new(0);
+
+pub fn toggle_view() {
+	VIEW.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+		Some(n.wrapping_add(1) % (ChordView::VALUES.len() as u8))
+	})
+	.unwrap();
+}
+
+pub fn view() -> ChordView {
+	ChordView::VALUES[VIEW.load(Ordering::Relaxed) as usize]
+}
+
+/// How simultaneous notes are displayed.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ChordView {
+	/// C, E, G
+	Notes,
+	/// C major
+	Chords,
+	/// C major: C, E, G
+	Both,
+}
+
+impl ChordView {
+	pub const VALUES: [Self; 3] = [Self::Notes, Self::Chords, Self::Both];
+}
+
+/// Chord qualities as intervals above the root, in order of preference.
+const QUALITIES: [(&[u8], &str); 23] = [
+	(&[0, 4, 7], " major"),
+	(&[0, 3, 7], " minor"),
+	(&[0, 3, 6], " diminished"),
+	(&[0, 4, 8], " augmented"),
+	(&[0, 5, 7], "sus4"),
+	(&[0, 2, 7], "sus2"),
+	(&[0, 4, 7, 10], "7"),
+	(&[0, 4, 7, 11], "maj7"),
+	(&[0, 3, 7, 10], "m7"),
+	(&[0, 3, 7, 11], "m(maj7)"),
+	(&[0, 3, 6, 10], "m7♭5"),
+	(&[0, 3, 6, 9], "dim7"),
+	(&[0, 5, 7, 10], "7sus4"),
+	(&[0, 4, 7, 9], "6"),
+	(&[0, 3, 7, 9], "m6"),
+	(&[0, 2, 4, 7], "add9"),
+	(&[0, 2, 3, 7], "m(add9)"),
+	(&[0, 2, 4, 7, 10], "9"),
+	(&[0, 2, 4, 7, 11], "maj9"),
+	(&[0, 2, 3, 7, 10], "m9"),
+	// Sevenths with the fifth left out.
+	(&[0, 4, 10], "7"),
+	(&[0, 4, 11], "maj7"),
+	(&[0, 3, 10], "m7"),
+];
+
+/// A chord recognised from three or more notes.
+pub struct Chord {
+	root: u8,
+	bass: u8,
+	quality: usize,
+	key: Option<Key>,
+}
+
+impl Chord {
+	/// Names the chord formed by `notes`, if there is one.
+	pub fn from_notes(notes: &[Note]) -> Option<Self> {
+		let bass = notes.iter().min_by_key(|n| n.pitch())?;
+		let set = notes
+			.iter()
+			.fold(0_u16, |set, n| set | 1 << (n.pitch() % 12));
+		if set.count_ones() < 3 {
+			return None;
+		}
+
+		let bass_pc = bass.pitch() % 12;
+		let mut found = None;
+		for (i, (intervals, _)) in QUALITIES.iter().enumerate() {
+			for root in (0..12).filter(|pc| set & 1 << pc != 0) {
+				let mask = intervals
+					.iter()
+					.fold(0_u16, |m, n| m | 1 << ((root + n) % 12));
+				if mask != set {
+					continue;
+				}
+				// Prefer a root position reading.
+				if root == bass_pc {
+					found = Some((root, i));
+					break;
+				} else if found.is_none() {
+					found = Some((root, i));
+				}
+			}
+			if matches!(found, Some((root, _)) if root == bass_pc) {
+				break;
+			}
+		}
+
+		found.map(|(root, quality)| Self {
+			root,
+			bass: bass_pc,
+			quality,
+			key: bass.key(),
+		})
+	}
+}
+
+impl fmt::Display for Chord {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let suffix = QUALITIES[self.quality].1;
+		let suffix = names::symbols(suffix);
+		write!(f, "{}{}", note::pc_name(self.root, self.key), suffix)?;
+		if self.bass != self.root {
+			write!(f, "/{}", note::pc_name(self.bass, self.key))?;
+			// Only a third, fifth or seventh in the bass makes an inversion.
+			let inversion = match (self.bass + 12 - self.root) % 12 {
+				3 | 4 => "first",
+				6..=8 => "second",
+				9..=11 => "third",
+				_ => return Ok(()),
+			};
+			write!(f, " ({} inversion)", inversion)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Names the chord formed by `pitches`, spelled without a key.
+	fn name(pitches: &[u8]) -> Option<String> {
+		let notes = pitches.iter().map(|&p| Note::from(p)).collect::<Vec<_>>();
+		Chord::from_notes(&notes).map(|c| c.to_string())
+	}
+
+	#[test]
+	fn root_position() {
+		assert_eq!(name(&[48, 52, 55]).as_deref(), Some("C major"));
+		assert_eq!(name(&[57, 60, 64]).as_deref(), Some("A minor"));
+		assert_eq!(name(&[55, 59, 62, 65]).as_deref(), Some("G7"));
+		// Doubled notes and spread voicings name the same chord.
+		assert_eq!(name(&[36, 55, 64, 72]).as_deref(), Some("C major"));
+	}
+
+	#[test]
+	fn inversions() {
+		assert_eq!(
+			name(&[52, 55, 60]).as_deref(),
+			Some("C major/E (first inversion)")
+		);
+		assert_eq!(
+			name(&[55, 60, 64]).as_deref(),
+			Some("C major/G (second inversion)")
+		);
+		assert_eq!(
+			name(&[53, 55, 59, 62]).as_deref(),
+			Some("G7/F (third inversion)")
+		);
+	}
+
+	#[test]
+	fn sevenths_without_the_fifth() {
+		assert_eq!(name(&[48, 52, 58]).as_deref(), Some("C7"));
+		assert_eq!(name(&[48, 52, 59]).as_deref(), Some("Cmaj7"));
+		assert_eq!(name(&[50, 53, 60]).as_deref(), Some("Dm7"));
+	}
+
+	#[test]
+	fn sixths_are_preferred_over_inverted_sevenths() {
+		assert_eq!(name(&[48, 52, 55, 57]).as_deref(), Some("C6"));
+		assert_eq!(name(&[57, 60, 64, 67]).as_deref(), Some("Am7"));
+	}
+
+	#[test]
+	fn fewer_than_three_pitch_classes_are_not_chords() {
+		assert_eq!(name(&[48, 55]), None);
+		assert_eq!(name(&[48, 60, 67]), None);
+		assert_eq!(name(&[48, 49, 50]), None);
+	}
+}
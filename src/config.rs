@@ -3,13 +3,18 @@ use std::{borrow::Cow, error::Error, fmt, fs};
 use crossterm::event::KeyCode;
 use serde::{Deserialize, Serialize};
 
-use crate::Command;
+use crate::{names::Names, tab::Tab, Command, Jump};
 
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
 	pub colors: bool,
 	pub keys: Keys,
+	/// The instrument the tablature note style is for.
+	pub tab: Tab,
+	pub practice: Practice,
+	/// How the note styles name notes.
+	pub names: Names,
 }
 
 impl Default for Config {
@@ -17,6 +22,33 @@ impl Default for Config {
 		Self {
 			colors: true,
 			keys: Keys::default(),
+			tab: Tab::default(),
+			practice: Practice::default(),
+			names: Names::default(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct Practice {
+	/// Seconds of silence between the repetitions of a loop.
+	pub loop_pause: f32,
+	/// The speed the tempo trainer starts at, 1.0 being the original tempo.
+	pub trainer_start: f32,
+	/// How much the tempo trainer raises the speed at a time.
+	pub trainer_step: f32,
+	/// How many times the loop is played before each raise.
+	pub trainer_repetitions: u32,
+}
+
+impl Default for Practice {
+	fn default() -> Self {
+		Self {
+			loop_pause: 0.0,
+			trainer_start: 0.6,
+			trainer_step: 0.05,
+			trainer_repetitions: 2,
 		}
 	}
 }
@@ -28,15 +60,34 @@ pub struct Keys {
 	pub prev: KeyCode,
 	pub transpose_up: KeyCode,
 	pub transpose_down: KeyCode,
+	pub octave_up: KeyCode,
+	pub octave_down: KeyCode,
+	pub transpose_to: KeyCode,
 	pub speed_up: KeyCode,
 	pub speed_down: KeyCode,
 	pub reset: KeyCode,
 	pub replay: KeyCode,
+	pub play: KeyCode,
+	pub loop_start: KeyCode,
+	pub loop_end: KeyCode,
+	pub play_loop: KeyCode,
+	pub trainer: KeyCode,
+	pub metronome: KeyCode,
+	pub go_to: KeyCode,
+	pub first: KeyCode,
+	pub last: KeyCode,
+	pub skip_forward: KeyCode,
+	pub skip_back: KeyCode,
+	pub next_section: KeyCode,
+	pub prev_section: KeyCode,
+	pub go_to_section: KeyCode,
+	pub bookmark: KeyCode,
 	pub solo: KeyCode,
 	pub silence: KeyCode,
 	pub rewind: KeyCode,
 	pub info: KeyCode,
 	pub note_style: KeyCode,
+	pub chord_view: KeyCode,
 	pub exit: KeyCode,
 	pub help: KeyCode,
 }
@@ -48,6 +99,9 @@ impl Default for Keys {
 			prev: KeyCode::Left,
 			transpose_up: KeyCode::Up,
 			transpose_down: KeyCode::Down,
+			octave_up: KeyCode::Char('u'),
+			octave_down: KeyCode::Char('d'),
+			transpose_to: KeyCode::Char('T'),
 			speed_up: KeyCode::Char('2'),
 			speed_down: KeyCode::Char('1'),
 			reset: KeyCode::Char('x'),
@@ -56,8 +110,24 @@ impl Default for Keys {
 			solo: KeyCode::Char('s'),
 			rewind: KeyCode::Char('p'),
 			replay: KeyCode::Char('r'),
+			play: KeyCode::Enter,
+			loop_start: KeyCode::Char('a'),
+			loop_end: KeyCode::Char('b'),
+			play_loop: KeyCode::Char('l'),
+			trainer: KeyCode::Char('t'),
+			metronome: KeyCode::Char('m'),
+			go_to: KeyCode::Char('g'),
+			first: KeyCode::Home,
+			last: KeyCode::End,
+			skip_forward: KeyCode::PageDown,
+			skip_back: KeyCode::PageUp,
+			next_section: KeyCode::Char(']'),
+			prev_section: KeyCode::Char('['),
+			go_to_section: KeyCode::Char('j'),
+			bookmark: KeyCode::Char('k'),
 			info: KeyCode::Char('i'),
 			note_style: KeyCode::Char('n'),
+			chord_view: KeyCode::Char('c'),
 			help: KeyCode::Char('h'),
 		}
 	}
@@ -78,15 +148,34 @@ impl fmt::Display for Keys {
 			("previous", self.prev),
 			("transpose up", self.transpose_up),
 			("transpose down", self.transpose_down),
+			("octave up", self.octave_up),
+			("octave down", self.octave_down),
+			("transpose to a key or instrument", self.transpose_to),
 			("speed up", self.speed_up),
 			("speed down", self.speed_down),
 			("reset transposition", self.reset),
 			("replay", self.replay),
+			("play from here", self.play),
+			("mark loop start", self.loop_start),
+			("mark loop end", self.loop_end),
+			("play loop", self.play_loop),
+			("tempo trainer", self.trainer),
+			("toggle metronome", self.metronome),
+			("go to bar", self.go_to),
+			("first bar", self.first),
+			("last bar", self.last),
+			("skip 10 bars forward", self.skip_forward),
+			("skip 10 bars back", self.skip_back),
+			("next section", self.next_section),
+			("previous section", self.prev_section),
+			("go to section", self.go_to_section),
+			("bookmark", self.bookmark),
 			("solo", self.solo),
 			("rewind", self.rewind),
 			("silence", self.silence),
 			("info", self.info),
 			("toggle note style", self.note_style),
+			("toggle chord view", self.chord_view),
 			("help", self.help),
 			("exit", self.exit),
 		];
@@ -143,10 +232,38 @@ impl Keys {
 			Command::Transpose(1)
 		} else if k == self.transpose_down {
 			Command::Transpose(-1)
+		} else if k == self.octave_up {
+			Command::Transpose(12)
+		} else if k == self.octave_down {
+			Command::Transpose(-12)
 		} else if k == self.reset {
 			Command::Transpose(0)
 		} else if k == self.replay {
 			Command::Replay
+		} else if k == self.play {
+			Command::PlayFromHere
+		} else if k == self.loop_start {
+			Command::LoopStart
+		} else if k == self.loop_end {
+			Command::LoopEnd
+		} else if k == self.play_loop {
+			Command::Loop
+		} else if k == self.trainer {
+			Command::Trainer
+		} else if k == self.metronome {
+			Command::Metronome
+		} else if k == self.first {
+			Command::Jump(Jump::First)
+		} else if k == self.last {
+			Command::Jump(Jump::Last)
+		} else if k == self.skip_forward {
+			Command::Jump(Jump::By(10))
+		} else if k == self.skip_back {
+			Command::Jump(Jump::By(-10))
+		} else if k == self.next_section {
+			Command::NextSection
+		} else if k == self.prev_section {
+			Command::PrevSection
 		} else if k == self.silence {
 			Command::Silence
 		} else if k == self.solo {
@@ -157,6 +274,8 @@ impl Keys {
 			Command::Info
 		} else if k == self.note_style {
 			Command::NoteStyle
+		} else if k == self.chord_view {
+			Command::ChordView
 		} else if k == self.speed_up {
 			Command::Speed(0.05)
 		} else if k == self.speed_down {
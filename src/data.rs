@@ -0,0 +1,64 @@
+use std::{
+	env,
+	error::Error,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+/// Returns the directory midnote keeps its data in.
+fn dir() -> Option<PathBuf> {
+	let base = if cfg!(windows) {
+		env::var_os("APPDATA").map(PathBuf::from)
+	} else {
+		env::var_os("XDG_DATA_HOME")
+			.filter(|s| !s.is_empty())
+			.map(PathBuf::from)
+			.or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".local/share")))
+	};
+	base.map(|p| p.join("midnote"))
+}
+
+/// Data about a MIDI file that is kept between runs.
+///
+/// Each kind of data is a JSON object in its own file, keyed by the canonical
+/// path of the MIDI file.
+pub struct Store {
+	path: PathBuf,
+	key: String,
+}
+
+impl Store {
+	/// Returns `None` if there is no data directory or `file` does not exist.
+	pub fn new(name: &str, file: &str) -> Option<Self> {
+		let key = fs::canonicalize(file).ok()?.to_string_lossy().into_owned();
+		Some(Self {
+			path: dir()?.join(format!("{}.json", name)),
+			key,
+		})
+	}
+
+	fn read_all(&self) -> Map<String, Value> {
+		fs::read_to_string(&self.path)
+			.ok()
+			.and_then(|s| serde_json::from_str(&s).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn load<T: DeserializeOwned>(&self) -> Option<T> {
+		let value = self.read_all().remove(&self.key)?;
+		serde_json::from_value(value).ok()
+	}
+
+	pub fn save<T: Serialize>(&self, data: &T) -> Result<(), Box<dyn Error>> {
+		let mut all = self.read_all();
+		all.insert(self.key.clone(), serde_json::to_value(data)?);
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(&self.path, serde_json::to_string_pretty(&all)?)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+use midly::MidiMessage;
+use nodi::{Event, Moment};
+
+/// The General MIDI percussion names, starting at key 35.
+const NAMES: [&str; 47] = [
+	"acoustic kick",
+	"kick",
+	"side stick",
+	"snare",
+	"hand clap",
+	"electric snare",
+	"low floor tom",
+	"closed hi-hat",
+	"high floor tom",
+	"pedal hi-hat",
+	"low tom",
+	"open hi-hat",
+	"low-mid tom",
+	"high-mid tom",
+	"crash",
+	"high tom",
+	"ride",
+	"china",
+	"ride bell",
+	"tambourine",
+	"splash",
+	"cowbell",
+	"crash 2",
+	"vibraslap",
+	"ride 2",
+	"high bongo",
+	"low bongo",
+	"mute high conga",
+	"open high conga",
+	"low conga",
+	"high timbale",
+	"low timbale",
+	"high agogo",
+	"low agogo",
+	"cabasa",
+	"maracas",
+	"short whistle",
+	"long whistle",
+	"short guiro",
+	"long guiro",
+	"claves",
+	"high wood block",
+	"low wood block",
+	"mute cuica",
+	"open cuica",
+	"mute triangle",
+	"open triangle",
+];
+
+/// A hit on the drum channel.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Drum(pub u8);
+
+impl fmt::Display for Drum {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.0.checked_sub(35).and_then(|i| NAMES.get(i as usize)) {
+			Some(name) => f.write_str(name),
+			None => write!(f, "drum {}", self.0),
+		}
+	}
+}
+
+/// Returns the drums hit at `moment`.
+pub fn moment_drums(moment: &Moment) -> Option<Vec<Drum>> {
+	match moment {
+		Moment::Empty => None,
+		Moment::Events(events) => {
+			let mut buf = Vec::new();
+			for e in events {
+				match e {
+					Event::Midi(m) if m.channel == 9 => match m.message {
+						MidiMessage::NoteOn { key, vel } if vel > 0 => {
+							let d = Drum(key.as_int());
+							if !buf.contains(&d) {
+								buf.push(d);
+							}
+						}
+						_ => (),
+					},
+					_ => (),
+				}
+			}
+
+			if buf.is_empty() {
+				None
+			} else {
+				Some(buf)
+			}
+		}
+	}
+}
@@ -1,5 +1,8 @@
 mod helpers;
+mod markers;
 mod meta_events;
+mod programs;
+mod timecode;
 
 use std::{
 	error::Error,
@@ -7,11 +10,16 @@ use std::{
 	sync::mpsc::{self, Receiver},
 };
 
-use meta_events::extract_meta_events;
+pub(crate) use helpers::Selection;
+use meta_events::{extract_channels, extract_meta_events};
 use midly::{Format, Smf, Timing};
 use nodi::Sheet;
+use timecode::Timecode;
 
-use crate::{app, bar, config::Config, player::Player, Response};
+use crate::{
+	analysis::Analysis, app, bar, config::Config, data::Store, names, player::Player,
+	session::Session, tab, Response, Target,
+};
 
 pub struct Args {
 	pub config: Config,
@@ -35,34 +43,101 @@ impl Args {
 		if m.is_present("no-color") {
 			config.colors = false;
 		}
+		tab::set(config.tab.clone());
+		names::set(&config.names);
 
-		let data = m.value_of("file").map(fs::read).unwrap()?;
+		let file = m.value_of("file").unwrap();
+		let data = fs::read(file)?;
 		let device_no = m.value_of("device").unwrap().parse::<usize>()?;
 		let con = helpers::get_midi(device_no)?;
 
 		let Smf { tracks, header } = Smf::parse(&data)?;
 
-		let tpb = match header.timing {
-			Timing::Metrical(n) => u16::from(n),
-			_ => return Err("the midi file has an unsupported time format".into()),
+		let bar_length = m
+			.value_of("bar-length")
+			.map(|s| s.parse::<f64>())
+			.transpose()?;
+		let (tpb, timecode) = match header.timing {
+			// The file sets its own tempo, so a bar length would not be used.
+			Timing::Metrical(_) if bar_length.is_some() => {
+				return Err("--bar-length only applies to files timed in SMPTE frames".into());
+			}
+			Timing::Metrical(n) => (u16::from(n), None),
+			Timing::Timecode(fps, subframe) => {
+				let t = Timecode::new(fps, subframe, &tracks, bar_length)?;
+				(t.tpb, Some(t))
+			}
 		};
 
 		let (sender, receiver) = mpsc::channel();
 
-		let (all, sheet) = match header.format {
-			Format::Parallel => {
-				let all = Sheet::parallel(&tracks);
-				let mut sheet = helpers::choose_track(&tracks[0..]);
+		let sessions = Store::new("sessions", file);
+		let session = if m.is_present("no-resume") {
+			None
+		} else {
+			sessions
+				.as_ref()
+				.and_then(|s| s.load::<Session>())
+				.filter(|s| s.selection.fits(header.format, &tracks))
+		};
+
+		let all = match header.format {
+			Format::Parallel => Sheet::parallel(&tracks),
+			Format::SingleTrack | Format::Sequential => Sheet::sequential(&tracks),
+		};
+		let selection = match &session {
+			Some(s) => s.selection.clone(),
+			None => match header.format {
+				Format::Parallel => helpers::choose_track(&tracks[0..]),
+				Format::SingleTrack => helpers::choose_channels(&tracks),
+				Format::Sequential => Selection::All,
+			},
+		};
+
+		let sheet = match &selection {
+			Selection::All => all.clone(),
+			Selection::Track(n) => {
+				let mut sheet = Sheet::single(&tracks[*n]);
 				sheet.merge_with(extract_meta_events(&all));
-				(all, sheet)
-			}
-			_ => {
-				let sheet = Sheet::sequential(&tracks);
-				(sheet.clone(), sheet)
+				sheet
 			}
+			Selection::Drums => extract_channels(&all, &[9]),
+			Selection::Channels(channels) => extract_channels(&all, channels),
 		};
+		let drums = matches!(selection, Selection::Drums);
 
-		let player = Player::new(con, sender, bar::bars(all, tpb), bar::bars(sheet, tpb));
+		let (mut all, mut sheet) = (all, sheet);
+		if let Some(t) = &timecode {
+			t.apply(&mut all);
+			t.apply(&mut sheet);
+		}
+
+		let mut all = bar::bars(all, tpb);
+		let mut solo = bar::bars(sheet, tpb);
+		let sections = markers::sections(&tracks, !matches!(header.format, Format::Parallel), &all);
+
+		// Key signatures are often missing, so fall back to the notes played.
+		let analysis = Analysis::new(&all);
+		for bars in [&mut all, &mut solo] {
+			for (i, b) in bars.iter_mut().enumerate() {
+				b.estimated_key = analysis.key_at(i);
+			}
+		}
+
+		let mut player = Player::new(con, sender, all, solo, drums, config.practice);
+		player.set_analysis(analysis);
+		player.set_sections(sections, Store::new("bookmarks", file));
+		if let Some(s) = &session {
+			player.restore(s);
+		}
+		player.keep_session(sessions, selection);
+		if let Some(target) = m.value_of("transpose") {
+			let target = target.parse::<Target>()?;
+			let bar = player.current_bar();
+			player
+				.transpose_to(target, bar)
+				.map_err(|e| format!("cannot transpose {}: {}", target, e))?;
+		}
 
 		Ok(Self {
 			player,
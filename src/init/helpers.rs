@@ -5,8 +5,10 @@ use std::{
 };
 
 use midir::{MidiOutput, MidiOutputConnection};
-use midly::{MetaMessage, TrackEvent, TrackEventKind};
-use nodi::Sheet;
+use midly::{Format, MetaMessage, MidiMessage, TrackEvent, TrackEventKind};
+use serde::{Deserialize, Serialize};
+
+use super::programs::PROGRAMS;
 
 pub fn list_devices() -> Result<(), Box<dyn Error>> {
 	let midi_out = MidiOutput::new("nodi")?;
@@ -95,31 +97,152 @@ impl fmt::Display for Meta {
 	}
 }
 
-pub fn choose_track(tracks: &[Vec<TrackEvent<'_>>]) -> Sheet {
+/// A part of the song to follow.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Selection {
+	/// The whole song.
+	All,
+	Track(usize),
+	/// Every event on the drum channel.
+	Drums,
+	/// Every event on the given channels.
+	Channels(Vec<u8>),
+}
+
+impl Selection {
+	/// Reports whether `self` could have been chosen for the given file, so that
+	/// a saved choice is not used with a file that has changed.
+	pub fn fits(&self, format: Format, tracks: &[Vec<TrackEvent<'_>>]) -> bool {
+		match (format, self) {
+			(Format::Parallel, Self::Track(n)) => *n < tracks.len(),
+			(Format::Parallel | Format::SingleTrack, Self::Drums) => has_drums(tracks),
+			(Format::SingleTrack, Self::Channels(chs)) => {
+				let channels = channels(tracks);
+				chs.iter().all(|ch| channels.iter().any(|c| c.0 == *ch))
+			}
+			(Format::SingleTrack | Format::Sequential, Self::All) => true,
+			_ => false,
+		}
+	}
+}
+
+fn has_drums(tracks: &[Vec<TrackEvent<'_>>]) -> bool {
+	tracks.iter().flatten().any(|e| {
+		matches!(
+			e.kind,
+			TrackEventKind::Midi {
+				channel,
+				message: MidiMessage::NoteOn { .. },
+			} if channel == 9
+		)
+	})
+}
+
+pub fn choose_track(tracks: &[Vec<TrackEvent<'_>>]) -> Selection {
 	let names = tracks.iter().map(|t| Meta::from_track(t));
 
 	for (i, name) in names.enumerate() {
 		println!("#{}: {}", i, &name);
 	}
 
+	let drums = has_drums(tracks);
+	if drums {
+		println!("#{}: Drums (channel 10)", tracks.len());
+	}
+	let max = if drums {
+		tracks.len()
+	} else {
+		tracks.len().saturating_sub(1)
+	};
+
 	loop {
-		print!("choose a track (0-{}): ", tracks.len());
+		print!("choose a track (0-{}): ", max);
 		io::stdout().flush().unwrap();
 		let stdin = io::stdin();
 		let n = stdin.lock().lines().next().unwrap().unwrap();
 
 		let n = match n.parse::<usize>() {
-			Err(_) => {
-				println!("please enter a number between 0 and {}", tracks.len());
+			Ok(n) if n <= max => n,
+			_ => {
+				println!("please enter a number between 0 and {}", max);
 				continue;
 			}
-			Ok(n) if n >= tracks.len() => {
-				println!("please enter a number between 0 and {}", tracks.len());
-				continue;
+		};
+
+		return if n == tracks.len() {
+			Selection::Drums
+		} else {
+			Selection::Track(n)
+		};
+	}
+}
+
+/// Returns the channels notes are played on, along with the first program set
+/// on each.
+fn channels(tracks: &[Vec<TrackEvent<'_>>]) -> Vec<(u8, Option<u8>)> {
+	let mut programs = [None; 16];
+	let mut used = [false; 16];
+	for e in tracks.iter().flatten() {
+		if let TrackEventKind::Midi { channel, message } = e.kind {
+			let ch = channel.as_int() as usize;
+			match message {
+				MidiMessage::NoteOn { .. } => used[ch] = true,
+				MidiMessage::ProgramChange { program } if programs[ch].is_none() => {
+					programs[ch] = Some(program.as_int());
+				}
+				_ => (),
 			}
-			Ok(n) => n,
+		}
+	}
+
+	(0..16)
+		.filter(|&ch| used[ch])
+		.map(|ch| (ch as u8, programs[ch]))
+		.collect()
+}
+
+pub fn choose_channels(tracks: &[Vec<TrackEvent<'_>>]) -> Selection {
+	let channels = channels(tracks);
+	match channels.as_slice() {
+		[] => return Selection::All,
+		[(9, _)] => return Selection::Drums,
+		[_] => return Selection::All,
+		_ => (),
+	}
+
+	for &(ch, program) in &channels {
+		let name = if ch == 9 {
+			"Drums"
+		} else {
+			program.map_or("Acoustic Grand Piano", |p| PROGRAMS[p as usize])
 		};
+		println!("#{}: {}", ch + 1, name);
+	}
 
-		return Sheet::single(&tracks[n]);
+	loop {
+		print!("choose one or more channels, separated by spaces: ");
+		io::stdout().flush().unwrap();
+		let stdin = io::stdin();
+		let line = stdin.lock().lines().next().unwrap().unwrap();
+
+		let chosen = line
+			.split(|c: char| c == ',' || c.is_whitespace())
+			.filter(|s| !s.is_empty())
+			.map(|s| {
+				s.parse::<u8>()
+					.ok()
+					.and_then(|n| n.checked_sub(1))
+					.filter(|n| channels.iter().any(|c| c.0 == *n))
+			})
+			.collect::<Option<Vec<_>>>();
+
+		return match chosen {
+			Some(chs) if chs == [9] => Selection::Drums,
+			Some(chs) if !chs.is_empty() => Selection::Channels(chs),
+			_ => {
+				println!("please enter channel numbers from the list above");
+				continue;
+			}
+		};
 	}
 }
@@ -0,0 +1,57 @@
+use midly::{MetaMessage, TrackEvent, TrackEventKind};
+
+use crate::{bar::Bar, section::Section};
+
+/// Collects the marker and cue point events in `tracks` as sections of `bars`.
+///
+/// With `sequential`, the tracks are played one after the other instead of
+/// together.
+pub fn sections(tracks: &[Vec<TrackEvent<'_>>], sequential: bool, bars: &[Bar]) -> Vec<Section> {
+	let mut buf = Vec::new();
+	let mut offset = 0_usize;
+	for track in tracks {
+		let mut tick = offset;
+		for e in track {
+			tick += u32::from(e.delta) as usize;
+			let text = match e.kind {
+				TrackEventKind::Meta(MetaMessage::Marker(s) | MetaMessage::CuePoint(s)) => s,
+				_ => continue,
+			};
+			let name = String::from_utf8_lossy(text).trim().to_string();
+			if !name.is_empty() {
+				buf.push((tick, name));
+			}
+		}
+		if sequential {
+			// nodi gives every track one tick past its last event.
+			offset = tick + 1;
+		}
+	}
+	buf.sort_by_key(|(tick, _)| *tick);
+
+	let mut sections = Vec::<Section>::new();
+	for (tick, name) in buf {
+		let bar = bar_at(bars, tick);
+		// Files often repeat markers on every track.
+		if !sections.iter().any(|s| s.bar == bar && s.name == name) {
+			sections.push(Section {
+				bar,
+				name,
+				bookmark: false,
+			});
+		}
+	}
+	sections
+}
+
+/// Returns the index of the bar `tick` falls in.
+fn bar_at(bars: &[Bar], tick: usize) -> usize {
+	let mut start = 0;
+	for (i, bar) in bars.iter().enumerate() {
+		start += bar.moments.len();
+		if tick < start {
+			return i;
+		}
+	}
+	bars.len().saturating_sub(1)
+}
@@ -16,3 +16,25 @@ pub fn extract_meta_events(sheet: &Sheet) -> Sheet {
 
 	sheet
 }
+
+/// Returns a copy of `sheet` keeping only the MIDI events on the given channels,
+/// along with every meta event.
+pub fn extract_channels(sheet: &Sheet, channels: &[u8]) -> Sheet {
+	let mut sheet = sheet.clone();
+	for m in sheet.iter_mut() {
+		match m {
+			Moment::Empty => {}
+			Moment::Events(events) => {
+				events.retain(|e| match e {
+					Event::Midi(m) => channels.contains(&m.channel.as_int()),
+					_ => true,
+				});
+				if events.is_empty() {
+					*m = Moment::Empty;
+				}
+			}
+		}
+	}
+
+	sheet
+}
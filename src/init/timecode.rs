@@ -0,0 +1,75 @@
+use std::error::Error;
+
+use midly::{Fps, MetaMessage, TrackEvent, TrackEventKind};
+use nodi::{Event, Moment, Sheet};
+
+/// Lays a beat grid over a file timed in SMPTE frames.
+pub struct Timecode {
+	pub tpb: u16,
+	/// Microseconds per beat, chosen so that every tick lasts exactly as long as
+	/// a subframe.
+	tempo: u32,
+	/// Whether bars are a fixed length, ignoring time signatures.
+	fixed: bool,
+}
+
+impl Timecode {
+	/// Derives the beat length from the first tempo event in `tracks`, or from
+	/// `bar_length` (in seconds, for a 4/4 bar) if given.
+	pub fn new(
+		fps: Fps,
+		subframe: u8,
+		tracks: &[Vec<TrackEvent<'_>>],
+		bar_length: Option<f64>,
+	) -> Result<Self, Box<dyn Error>> {
+		let ticks_per_sec = fps.as_f32() as f64 * subframe as f64;
+		let beat_secs = match bar_length {
+			Some(secs) => secs / 4.0,
+			None => {
+				let tempo = tracks
+					.iter()
+					.flatten()
+					.find_map(|e| match e.kind {
+						TrackEventKind::Meta(MetaMessage::Tempo(n)) => Some(u32::from(n)),
+						_ => None,
+					})
+					.unwrap_or(500_000);
+				tempo as f64 / 1e6
+			}
+		};
+
+		let tpb = (ticks_per_sec * beat_secs).round();
+		if !(1.0..=u16::MAX as f64).contains(&tpb) {
+			return Err("the bar length is out of range for this file".into());
+		}
+
+		Ok(Self {
+			tpb: tpb as u16,
+			tempo: (tpb * 1e6 / ticks_per_sec).round() as u32,
+			fixed: bar_length.is_some(),
+		})
+	}
+
+	/// Replaces the tempo map of `sheet` with a single tempo so that it plays in
+	/// real time. With a fixed bar length, time signatures are dropped as well.
+	pub fn apply(&self, sheet: &mut Sheet) {
+		let fixed = self.fixed;
+		for m in sheet.iter_mut() {
+			if let Moment::Events(events) = m {
+				events.retain(|e| match e {
+					Event::Tempo(_) => false,
+					Event::TimeSignature(..) => !fixed,
+					_ => true,
+				});
+				if events.is_empty() {
+					*m = Moment::Empty;
+				}
+			}
+		}
+
+		if sheet.is_empty() {
+			sheet.push(Moment::Empty);
+		}
+		sheet[0].push(Event::Tempo(self.tempo));
+	}
+}
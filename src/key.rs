@@ -0,0 +1,201 @@
+use std::{fmt, str::FromStr};
+
+use crate::note;
+
+/// A key signature, as declared in a MIDI file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Key {
+	/// Positive for sharps, negative for flats.
+	pub sharps: i8,
+	pub minor: bool,
+}
+
+impl Key {
+	/// Returns the pitch class of the tonic.
+	pub fn tonic(self) -> u8 {
+		let major = (self.sharps as i32 * 7).rem_euclid(12);
+		if self.minor {
+			((major + 9) % 12) as u8
+		} else {
+			major as u8
+		}
+	}
+
+	/// Returns the key with the tonic `pc`, spelled with as few accidentals as
+	/// possible.
+	pub fn from_tonic(pc: u8, minor: bool) -> Self {
+		(-6..=6)
+			.map(|sharps| Self { sharps, minor })
+			.filter(|k| k.tonic() == pc % 12)
+			.min_by_key(|k| k.sharps.abs())
+			.unwrap()
+	}
+
+	/// Returns the key `shift` semitones away, spelled with as few accidentals
+	/// as possible.
+	pub fn transpose(self, shift: i8) -> Self {
+		if shift % 12 == 0 {
+			return self;
+		}
+
+		let pc = (self.sharps as i32 * 7 + shift as i32).rem_euclid(12);
+		// F# and G♭ are equally far; stay on the side of the original key.
+		let sharps = (-5..=6)
+			.map(|n| if n == 6 && self.sharps < 0 { -6 } else { n })
+			.find(|n| (n * 7_i32).rem_euclid(12) == pc)
+			.unwrap();
+
+		Self {
+			sharps: sharps as i8,
+			minor: self.minor,
+		}
+	}
+
+	/// Returns the letter (0 for C through 6 for B) and the accidental (-1 for
+	/// a flat, 1 for a sharp) the pitch class `pc` is written with.
+	///
+	/// Without a key, C#, E♭, F#, A♭ and B♭ are used. In minor keys, the raised
+	/// sixth and seventh are written as such, like G# in A minor.
+	pub fn spell(key: Option<Self>, pc: u8) -> (u8, i8) {
+		// The letter of every pitch class, raised with a sharp if needed.
+		const LETTERS: [u8; 12] = [0, 0, 1, 1, 2, 3, 3, 4, 4, 5, 5, 6];
+		let sharps = key.map_or(0, |k| k.sharps);
+		let pc = pc % 12;
+
+		if let Some(raised) = key.and_then(|k| k.raised(pc)) {
+			return raised;
+		}
+
+		match pc {
+			5 if sharps >= 6 => (2, 1),
+			0 if sharps >= 7 => (6, 1),
+			11 if sharps <= -6 => (0, -1),
+			4 if sharps <= -7 => (3, -1),
+			0 | 2 | 4 | 5 | 7 | 9 | 11 => (LETTERS[pc as usize], 0),
+			1 | 6 if sharps == 0 => (LETTERS[pc as usize], 1),
+			_ if sharps > 0 => (LETTERS[pc as usize], 1),
+			_ => (LETTERS[pc as usize] + 1, -1),
+		}
+	}
+
+	/// Spells `pc` as the raised sixth or seventh degree of a minor key, unless
+	/// that takes a double sharp.
+	fn raised(self, pc: u8) -> Option<(u8, i8)> {
+		// The pitch class of every natural note, C through B.
+		const NATURALS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+		if !self.minor {
+			return None;
+		}
+		let tonic = self.tonic();
+		let degree = match (tonic + 12 - pc) % 12 {
+			1 => 6,
+			3 => 5,
+			_ => return None,
+		};
+		// The tonic is in the scale of the relative major.
+		let (tonic_letter, _) = Self::spell(
+			Some(Self {
+				minor: false,
+				..self
+			}),
+			tonic,
+		);
+		let letter = (tonic_letter + degree) % 7;
+		match (pc + 12 - NATURALS[letter as usize]) % 12 {
+			0 => Some((letter, 0)),
+			1 => Some((letter, 1)),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Key {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let (letter, accidental) = Self::spell(Some(*self), self.tonic());
+		let mode = if self.minor { "minor" } else { "major" };
+		write!(f, "{} {}", note::letter_name(letter, accidental), mode)
+	}
+}
+
+impl FromStr for Key {
+	type Err = &'static str;
+
+	/// Parses names like "G", "F# minor", "Bb major" or "ebm".
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		const ERR: &str = "expected a key such as 'G major' or 'F# minor'";
+		let s = s.trim().to_lowercase().replace('♭', "b").replace('♯', "#");
+		let mut chars = s.chars();
+		let letter = match chars.next().ok_or(ERR)? {
+			c @ 'c'..='g' => c as u8 - b'c',
+			c @ 'a'..='b' => c as u8 - b'a' + 5,
+			_ => return Err(ERR),
+		};
+		let rest = chars.as_str().trim_start();
+		let (acc, rest) = if let Some(rest) = rest.strip_prefix('#') {
+			(1, rest)
+		} else if let Some(rest) = rest.strip_prefix("sharp") {
+			(1, rest)
+		} else if let Some(rest) = rest.strip_prefix("flat") {
+			(-1, rest)
+		} else if let Some(rest) = rest.strip_prefix('b') {
+			(-1, rest)
+		} else {
+			(0, rest)
+		};
+		let minor = match rest.trim() {
+			"" | "major" | "maj" => false,
+			"m" | "minor" | "min" => true,
+			_ => return Err(ERR),
+		};
+
+		// The natural pitch classes of C through B.
+		const NATURALS: [i8; 7] = [0, 2, 4, 5, 7, 9, 11];
+		let pc = (NATURALS[letter as usize] + acc).rem_euclid(12) as u8;
+		// Keep the spelling asked for if there is such a key, as with C# and D♭.
+		Ok((-7..=7)
+			.map(|sharps| Self { sharps, minor })
+			.find(|k| k.tonic() == pc && Self::spell(Some(*k), pc) == (letter, acc))
+			.unwrap_or_else(|| Self::from_tonic(pc, minor)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn spell(key: &str, pc: u8) -> (u8, i8) {
+		Key::spell(Some(key.parse().unwrap()), pc)
+	}
+
+	#[test]
+	fn spells_from_the_key_signature() {
+		assert_eq!(Key::spell(None, 3), (2, -1));
+		assert_eq!(Key::spell(None, 6), (3, 1));
+		assert_eq!(spell("G major", 6), (3, 1));
+		assert_eq!(spell("F major", 10), (6, -1));
+		assert_eq!(spell("Eb major", 8), (5, -1));
+		assert_eq!(spell("C# major", 0), (6, 1));
+		assert_eq!(spell("Gb major", 11), (0, -1));
+	}
+
+	#[test]
+	fn raises_the_sixth_and_seventh_in_minor() {
+		assert_eq!(spell("A minor", 8), (4, 1));
+		assert_eq!(spell("A minor", 6), (3, 1));
+		assert_eq!(spell("D minor", 1), (0, 1));
+		assert_eq!(spell("D minor", 11), (6, 0));
+		assert_eq!(spell("G minor", 6), (3, 1));
+		assert_eq!(spell("G minor", 4), (2, 0));
+		assert_eq!(spell("C minor", 11), (6, 0));
+		assert_eq!(spell("F minor", 4), (2, 0));
+		// The flat sixth and seventh keep the key signature.
+		assert_eq!(spell("D minor", 10), (6, -1));
+		assert_eq!(spell("C minor", 8), (5, -1));
+	}
+
+	#[test]
+	fn double_sharps_are_avoided() {
+		// F double sharp in G# minor.
+		assert_eq!(spell("G# minor", 7), (4, 0));
+	}
+}
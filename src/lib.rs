@@ -1,36 +1,119 @@
+mod analysis;
 mod app;
 pub(crate) mod bar;
+mod chord;
 pub mod config;
+mod data;
+mod drum;
 pub mod init;
+mod key;
+mod metronome;
+pub mod names;
 mod note;
+mod output;
 pub mod player;
+mod rhythm;
+mod section;
+mod session;
+pub mod tab;
+mod target;
 
 use std::fmt;
 
-pub use note::{moment_notes, Note};
-pub type Notes = Vec<Vec<Note>>;
+pub use analysis::Analysis;
+pub use drum::Drum;
+pub use key::Key;
+pub use metronome::Metronome;
+pub use note::{moment_notes, Element, Note, TimedNote};
+pub use rhythm::{Beat, Length, TimeSignature};
+pub use target::Target;
 
+/// The notes in a bar.
+#[derive(Default)]
+pub struct Notes {
+	/// Set when the meter of the bar differs from the one before it.
+	pub time_sig: Option<TimeSignature>,
+	/// The names of the sections starting at this bar.
+	pub sections: Vec<String>,
+	pub elements: Vec<(Beat, Element)>,
+	/// How many notes were left out because the transposition takes them out of
+	/// the MIDI range.
+	pub out_of_range: usize,
+}
+
+#[derive(Clone)]
 pub struct State {
 	pub transposition: i8,
+	/// What the transposition was chosen for, if it was.
+	pub target: Option<Target>,
+	/// The current bar, counting from 0. Bars are displayed counting from 1.
 	pub index: usize,
 	pub length: usize,
 	pub solo: bool,
 	pub speed: f32,
+	pub time_sig: TimeSignature,
+	pub key: Option<Key>,
+	/// The key the notes around the bar suggest.
+	pub estimated_key: Option<Key>,
+	/// The keys of the whole song, sent for the info command only.
+	pub analysis: Option<Analysis>,
+	/// The first and the last bar of the loop, if marked.
+	pub loop_range: Option<(usize, usize)>,
+	pub metronome: Metronome,
 }
 
 pub enum Command {
 	Next,
 	Prev,
 	Replay,
+	/// Plays every bar from the current one on, until another command is given.
+	PlayFromHere,
+	/// Marks the current bar as the start of the loop.
+	LoopStart,
+	/// Marks the current bar as the end of the loop.
+	LoopEnd,
+	/// Plays the bars between the loop marks over and over.
+	Loop,
+	/// Loops like [Command::Loop], starting slow and speeding up gradually.
+	Trainer,
 	Silence,
 	Reset,
 	Solo,
+	/// Transposes by the given semitones, up to 4 octaves either way.
 	/// Transpose(0) will reset it instead
 	Transpose(i8),
+	TransposeTo(Target),
 	Info,
 	/// Changes the speed by the value given.
 	Speed(f32),
 	NoteStyle,
+	/// Cycles between showing notes, chord names or both.
+	ChordView,
+	/// Cycles the metronome between off, on and on with a count-in.
+	Metronome,
+	/// Plays the bar jumped to.
+	Jump(Jump),
+	NextSection,
+	PrevSection,
+	/// Jumps to the section with the given name, or the first one starting with
+	/// it.
+	GoToSection(String),
+	/// Bookmarks the current bar with the given name. An empty name removes the
+	/// bookmarks on the current bar.
+	Bookmark(String),
+	/// Saves the session and stops the player.
+	Quit,
+}
+
+#[derive(Copy, Clone)]
+pub enum Jump {
+	/// The bar at this index, counting from 0.
+	To(usize),
+	First,
+	Last,
+	/// Bars to skip from the current one, backwards if negative. Stops at the
+	/// first and the last bar.
+	By(isize),
 }
 
 pub enum Response {
@@ -38,19 +121,74 @@ pub enum Response {
 	StartOfTrack,
 	Notes(Notes),
 	State(State),
+	Message(String),
 }
 
 impl fmt::Display for State {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(
 			f,
-			"{index} / {length}
-transposition = {trans:+} | solo = {solo} | speed = {speed}%",
-			index = self.index,
+			"{index} / {length}\ntransposition = {trans:+}",
+			index = self.index + 1,
 			length = self.length,
 			trans = self.transposition,
+		)?;
+		if let Some(target) = self.target {
+			write!(f, " ({})", target)?;
+		}
+		write!(
+			f,
+			" | solo = {solo} | speed = {speed:.0}% | time signature = {time_sig}",
 			solo = if self.solo { "on" } else { "off" },
 			speed = self.speed * 100.0,
-		)
+			time_sig = self.time_sig,
+		)?;
+		if let Some(key) = self.key {
+			write!(f, " | key = {}", key)?;
+		}
+		match self.estimated_key {
+			Some(k) if self.key != Some(k) => write!(f, " | estimated key = {}", k)?,
+			_ => (),
+		}
+		write!(f, " | metronome = {}", self.metronome)?;
+		if let Some((start, end)) = self.loop_range {
+			write!(f, " | loop = {} - {}", start + 1, end + 1)?;
+		}
+		if let Some(a) = &self.analysis {
+			if let Some(key) = a.key {
+				write!(f, "\nthe song seems to be in {}", key)?;
+			}
+			for (n, key) in a.changes.iter().skip(1) {
+				write!(f, "\nprobable modulation to {} at bar {}", key, n + 1)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn speed_is_shown_in_whole_percents() {
+		let mut state = State {
+			transposition: 0,
+			target: None,
+			index: 0,
+			length: 1,
+			solo: false,
+			speed: 0.6,
+			time_sig: TimeSignature::default(),
+			key: None,
+			estimated_key: None,
+			analysis: None,
+			loop_range: None,
+			metronome: Metronome::Off,
+		};
+		for _ in 0..3 {
+			state.speed += 0.05;
+		}
+		assert!(state.to_string().contains("| speed = 75% |"));
 	}
 }
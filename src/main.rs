@@ -8,12 +8,12 @@ use std::{
 };
 
 use crossterm::{
-	event::{self, Event},
+	event::{self, Event, KeyCode},
 	style::{Attribute, Color, Stylize},
 	terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 	ExecutableCommand,
 };
-use midnote::{init::Args, Note, Response};
+use midnote::{init::Args, Command, Jump, Notes, Response, Target};
 
 const CLEAR: Clear = Clear(ClearType::All);
 
@@ -25,11 +25,11 @@ fn run(
 	}: Args,
 ) -> Result<(), Box<dyn Error>> {
 	let (commands, commands_recv) = mpsc::channel();
-	thread::spawn(move || {
+	let player = thread::spawn(move || {
 		player.start(commands_recv);
 	});
 	start_display(response, config.colors);
-	print(&config.keys).unwrap();
+	print(config.keys).unwrap();
 
 	let keys = config.keys;
 
@@ -45,23 +45,81 @@ fn run(
 		}
 
 		if k == keys.exit {
+			commands.send(Command::Quit)?;
+			player.join().ok();
 			break;
 		} else if k == keys.help {
-			print(&keys).unwrap();
+			print(keys).unwrap();
+		} else if k == keys.go_to {
+			match read_bar(config.colors) {
+				Some(n) => commands.send(Command::Jump(Jump::To(n)))?,
+				None => print_color("Cancelled.", config.colors),
+			}
+		} else if k == keys.go_to_section {
+			match read_line("go to section: ", config.colors, |_| true) {
+				Some(s) if !s.trim().is_empty() => commands.send(Command::GoToSection(s))?,
+				_ => print_color("Cancelled.", config.colors),
+			}
+		} else if k == keys.transpose_to {
+			match read_line("transpose to: ", config.colors, |_| true) {
+				Some(s) if !s.trim().is_empty() => match s.parse::<Target>() {
+					Ok(t) => commands.send(Command::TransposeTo(t))?,
+					Err(e) => print_color(e, config.colors),
+				},
+				_ => print_color("Cancelled.", config.colors),
+			}
+		} else if k == keys.bookmark {
+			match read_line("bookmark name (empty to remove): ", config.colors, |_| true) {
+				Some(s) => commands.send(Command::Bookmark(s))?,
+				None => print_color("Cancelled.", config.colors),
+			}
 		}
 	}
 
 	Ok(())
 }
 
-fn print_notes(notes: &[Vec<Note>], colors: bool) {
-	if notes.is_empty() {
+/// Reads a line of the characters `accept` allows, returning `None` if
+/// cancelled.
+fn read_line(prompt: &str, colors: bool, accept: fn(char) -> bool) -> Option<String> {
+	let mut input = String::new();
+	loop {
+		print_color(&format!("{}{}", prompt, input), colors);
+		let k = match event::read() {
+			Ok(Event::Key(k)) => k.code,
+			_ => continue,
+		};
+		match k {
+			KeyCode::Char(c) if accept(c) => input.push(c),
+			KeyCode::Backspace => {
+				input.pop();
+			}
+			KeyCode::Enter => return Some(input),
+			KeyCode::Esc => return None,
+			_ => (),
+		}
+	}
+}
+
+/// Prompts for a bar number, returning its index.
+fn read_bar(colors: bool) -> Option<usize> {
+	let input = read_line("go to bar: ", colors, |c| c.is_ascii_digit())?;
+	// Bars are numbered from 1.
+	input.parse::<usize>().ok()?.checked_sub(1)
+}
+
+fn print_notes(notes: &Notes, colors: bool) {
+	if notes.elements.is_empty()
+		&& notes.time_sig.is_none()
+		&& notes.sections.is_empty()
+		&& notes.out_of_range == 0
+	{
 		if colors {
 			let s = "---"
 				.with(Color::Grey)
 				.attribute(Attribute::Bold)
 				.on(Color::Black);
-			print(&s)
+			print(s)
 		} else {
 			print("---")
 		}
@@ -71,14 +129,19 @@ fn print_notes(notes: &[Vec<Note>], colors: bool) {
 
 	let mut buf = String::new();
 
-	for ns in notes {
-		for (i, n) in ns.iter().enumerate() {
-			if i > 0 {
-				buf.push_str(", ");
-			}
-			write!(&mut buf, "{}", n).unwrap();
-		}
-		buf.push('\n');
+	for name in &notes.sections {
+		writeln!(&mut buf, "section {}", name).unwrap();
+	}
+	if let Some(ts) = notes.time_sig {
+		writeln!(&mut buf, "time signature {}", ts).unwrap();
+	}
+	for (beat, e) in &notes.elements {
+		writeln!(&mut buf, "{}: {}", beat, e).unwrap();
+	}
+	match notes.out_of_range {
+		0 => (),
+		1 => writeln!(&mut buf, "warning: 1 note is out of range").unwrap(),
+		n => writeln!(&mut buf, "warning: {} notes are out of range", n).unwrap(),
 	}
 
 	if colors {
@@ -86,7 +149,7 @@ fn print_notes(notes: &[Vec<Note>], colors: bool) {
 			.with(Color::Cyan)
 			.attribute(Attribute::Bold)
 			.on(Color::Black);
-		print(&s)
+		print(s)
 	} else {
 		print(&buf)
 	}
@@ -96,7 +159,7 @@ fn print_notes(notes: &[Vec<Note>], colors: bool) {
 fn print_color(s: &str, colors: bool) {
 	if colors {
 		let s = s.on(Color::Black).with(Color::Yellow);
-		print(&s)
+		print(s)
 	} else {
 		print(s)
 	}
@@ -110,6 +173,7 @@ fn start_display(response: Receiver<Response>, colors: bool) {
 				Response::State(s) => print_color(&s.to_string(), colors),
 				Response::StartOfTrack => print_color("Start of track.", colors),
 				Response::EndOfTrack => print_color("End of track.", colors),
+				Response::Message(s) => print_color(&s, colors),
 				Response::Notes(notes) => {
 					// This sleep prevents the screen reader from glitching.
 					thread::sleep(Duration::from_millis(50));
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use midly::{num::u4, MidiMessage};
+use nodi::{Event, MidiEvent, Moment};
+
+use crate::rhythm::TimeSignature;
+
+/// High wood block, for the first beat of a bar.
+const ACCENT: u8 = 76;
+/// Low wood block, for the other beats.
+const CLICK: u8 = 77;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Metronome {
+	Off,
+	On,
+	/// Clicks, with a bar of clicks alone before playback starts.
+	CountIn,
+}
+
+impl Metronome {
+	pub fn next(self) -> Self {
+		match self {
+			Self::Off => Self::On,
+			Self::On => Self::CountIn,
+			Self::CountIn => Self::Off,
+		}
+	}
+}
+
+impl fmt::Display for Metronome {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::Off => "off",
+			Self::On => "on",
+			Self::CountIn => "on with count-in",
+		})
+	}
+}
+
+fn midi(key: u8, vel: u8, on: bool) -> Event {
+	let (key, vel) = (key.into(), vel.into());
+	Event::Midi(MidiEvent {
+		channel: u4::new(9),
+		message: if on {
+			MidiMessage::NoteOn { key, vel }
+		} else {
+			MidiMessage::NoteOff { key, vel }
+		},
+	})
+}
+
+/// Adds a click on every beat of `time_sig` to `moments`, which start on a
+/// downbeat.
+pub fn add_clicks(moments: &mut [Moment], time_sig: TimeSignature, tpb: u16) {
+	let beat = time_sig.beat_ticks(tpb);
+	let len = moments.len();
+	for i in 0..time_sig.numerator {
+		let start = (beat * i as f64).round() as usize;
+		if start >= len {
+			break;
+		}
+		let (key, vel) = if i == 0 { (ACCENT, 110) } else { (CLICK, 80) };
+		moments[start].push(midi(key, vel, true));
+		let end = (start + (beat / 2.0).round() as usize).min(len - 1);
+		moments[end].push(midi(key, 0, false));
+	}
+}
+
+/// Returns a bar of `time_sig` with nothing but clicks in it.
+pub fn count_in(time_sig: TimeSignature, tpb: u16) -> Vec<Moment> {
+	let len = (time_sig.beat_ticks(tpb) * time_sig.numerator as f64).round() as usize;
+	let mut moments = vec![Moment::Empty; len.max(1)];
+	add_clicks(&mut moments, time_sig, tpb);
+	moments
+}
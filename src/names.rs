@@ -0,0 +1,330 @@
+use std::{borrow::Cow, collections::HashMap, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+
+static NAMES: RwLock<Option<Spelled>> = RwLock::new(None);
+
+/// Sets the note names used by the note styles.
+pub fn set(names: &Names) {
+	*NAMES.write().unwrap() = Some(Spelled::new(names));
+}
+
+fn get<T>(f: impl FnOnce(&Spelled) -> T) -> T {
+	match &*NAMES.read().unwrap() {
+		Some(s) => f(s),
+		None => f(&Spelled::new(&Names::default())),
+	}
+}
+
+/// How notes are named.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Names {
+	/// The names used by the letter note styles and for keys.
+	pub letters: Table,
+	/// The names used by the fixed do note styles.
+	pub syllables: Table,
+	/// How sharps and flats are written by the tables that do not have their own.
+	pub accidentals: Accidentals,
+}
+
+impl Default for Names {
+	fn default() -> Self {
+		Self {
+			letters: Table::English,
+			syllables: Table::Solfege,
+			accidentals: Accidentals::Symbols,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Accidentals {
+	/// E♭, F#
+	Symbols,
+	/// E flat, F sharp
+	Words,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Table {
+	/// C, E♭, B
+	English,
+	/// C, Es, H, B for B♭
+	German,
+	/// C, Es, B, Bes
+	Dutch,
+	/// ハ, 変ホ, ロ
+	Japanese,
+	/// Do, Mi♭, Si
+	Solfege,
+	/// Sa, komal Ga, Ni; with Sa on C
+	Sargam,
+	Custom(Box<Rules>),
+}
+
+/// The rules a table names notes by.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rules {
+	/// The natural notes, C through B.
+	pub naturals: [String; 7],
+	/// Added to the natural for a sharp. Without one, the accidentals setting
+	/// is used.
+	#[serde(default)]
+	pub sharp: Option<String>,
+	/// Added to the natural for a flat, like `sharp`.
+	#[serde(default)]
+	pub flat: Option<String>,
+	/// Whether `sharp` and `flat` go before the natural instead of after it.
+	#[serde(default)]
+	pub prefix: bool,
+	/// Names that do not follow the rules, keyed by the name the rules give;
+	/// such as "Hes" for "B" in German.
+	#[serde(default)]
+	pub exceptions: HashMap<String, String>,
+}
+
+impl Rules {
+	fn new(
+		naturals: [&str; 7],
+		sharp: Option<&str>,
+		flat: Option<&str>,
+		prefix: bool,
+		exceptions: &[(&str, &str)],
+	) -> Self {
+		Self {
+			naturals: naturals.map(String::from),
+			sharp: sharp.map(String::from),
+			flat: flat.map(String::from),
+			prefix,
+			exceptions: exceptions
+				.iter()
+				.map(|&(k, v)| (k.to_string(), v.to_string()))
+				.collect(),
+		}
+	}
+
+	fn spell(&self, letter: usize, acc: i8, accidentals: Accidentals) -> String {
+		let natural = &self.naturals[letter];
+		let affix = match acc {
+			1 => &self.sharp,
+			-1 => &self.flat,
+			_ => return natural.clone(),
+		};
+		let name = match affix {
+			Some(a) if self.prefix => format!("{}{}", a, natural),
+			Some(a) => format!("{}{}", natural, a),
+			None => format!("{}{}", natural, accidental(acc, accidentals)),
+		};
+		self.exceptions.get(&name).cloned().unwrap_or(name)
+	}
+}
+
+impl Table {
+	fn rules(&self) -> Cow<'_, Rules> {
+		const LETTERS: [&str; 7] = ["C", "D", "E", "F", "G", "A", "B"];
+		let rules = match self {
+			Self::English => Rules::new(LETTERS, None, None, false, &[]),
+			Self::German => Rules::new(
+				["C", "D", "E", "F", "G", "A", "H"],
+				Some("is"),
+				Some("es"),
+				false,
+				&[("Ees", "Es"), ("Aes", "As"), ("Hes", "B")],
+			),
+			Self::Dutch => Rules::new(
+				LETTERS,
+				Some("is"),
+				Some("es"),
+				false,
+				&[("Ees", "Es"), ("Aes", "As")],
+			),
+			Self::Japanese => Rules::new(
+				["ハ", "ニ", "ホ", "ヘ", "ト", "イ", "ロ"],
+				Some("嬰"),
+				Some("変"),
+				true,
+				&[],
+			),
+			Self::Solfege => Rules::new(
+				["Do", "Re", "Mi", "Fa", "Sol", "La", "Si"],
+				None,
+				None,
+				false,
+				&[],
+			),
+			Self::Sargam => Rules::new(
+				["Sa", "Re", "Ga", "Ma", "Pa", "Dha", "Ni"],
+				Some("tivra "),
+				Some("komal "),
+				true,
+				&[],
+			),
+			Self::Custom(rules) => return Cow::Borrowed(rules),
+		};
+		Cow::Owned(rules)
+	}
+}
+
+fn accidental(acc: i8, accidentals: Accidentals) -> &'static str {
+	match (acc, accidentals) {
+		(1, Accidentals::Symbols) => "#",
+		(-1, Accidentals::Symbols) => "♭",
+		(1, Accidentals::Words) => " sharp",
+		(-1, Accidentals::Words) => " flat",
+		_ => "",
+	}
+}
+
+/// Every spelling of every note in both tables, worked out once.
+struct Spelled {
+	/// Indexed by the letter (0 for C), then the accidental plus one.
+	letters: Vec<[String; 3]>,
+	syllables: Vec<[String; 3]>,
+	accidentals: Accidentals,
+}
+
+impl Spelled {
+	fn new(names: &Names) -> Self {
+		let spell_all = |table: &Table| {
+			let rules = table.rules();
+			(0..7)
+				.map(|l| [-1, 0, 1].map(|acc| rules.spell(l, acc, names.accidentals)))
+				.collect()
+		};
+		Self {
+			letters: spell_all(&names.letters),
+			syllables: spell_all(&names.syllables),
+			accidentals: names.accidentals,
+		}
+	}
+}
+
+/// Returns the name of the note with the `letter` (0 for C through 6 for B)
+/// and the accidental `acc` (-1 for flat, 1 for sharp).
+pub fn letter(letter: u8, acc: i8) -> String {
+	get(|s| s.letters[letter as usize][(acc + 1) as usize].clone())
+}
+
+/// Like `letter`, but with the names of the fixed do styles.
+pub fn syllable(letter: u8, acc: i8) -> String {
+	get(|s| s.syllables[letter as usize][(acc + 1) as usize].clone())
+}
+
+/// Returns a scale degree such as "♭3" or "flat 3".
+pub fn degree(n: u8, acc: i8) -> String {
+	degree_with(n, acc, get(|s| s.accidentals))
+}
+
+fn degree_with(n: u8, acc: i8, accidentals: Accidentals) -> String {
+	match accidentals {
+		Accidentals::Words if acc != 0 => {
+			format!("{} {}", accidental(acc, Accidentals::Words).trim(), n)
+		}
+		a => format!("{}{}", accidental(acc, a), n),
+	}
+}
+
+/// Replaces the accidental symbols in `s` with words if they are set to be
+/// written as words.
+pub fn symbols(s: &str) -> Cow<'_, str> {
+	symbols_with(s, get(|s| s.accidentals))
+}
+
+fn symbols_with(s: &str, accidentals: Accidentals) -> Cow<'_, str> {
+	match accidentals {
+		Accidentals::Words if s.contains(['♭', '#']) => s
+			.replace('♭', " flat ")
+			.replace('#', " sharp ")
+			.split_whitespace()
+			.collect::<Vec<_>>()
+			.join(" ")
+			.into(),
+		_ => s.into(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Spells every note of `table`, flat, natural and sharp for C through B.
+	fn spell_all(table: Table, accidentals: Accidentals) -> Vec<[String; 3]> {
+		Spelled::new(&Names {
+			letters: table,
+			syllables: Table::Solfege,
+			accidentals,
+		})
+		.letters
+	}
+
+	#[test]
+	fn german() {
+		let names = spell_all(Table::German, Accidentals::Symbols);
+		assert_eq!(names[6], ["B", "H", "His"]);
+		assert_eq!(names[2], ["Es", "E", "Eis"]);
+		assert_eq!(names[5], ["As", "A", "Ais"]);
+		assert_eq!(names[0], ["Ces", "C", "Cis"]);
+	}
+
+	#[test]
+	fn dutch() {
+		let names = spell_all(Table::Dutch, Accidentals::Symbols);
+		assert_eq!(names[2], ["Es", "E", "Eis"]);
+		assert_eq!(names[5], ["As", "A", "Ais"]);
+		assert_eq!(names[6], ["Bes", "B", "Bis"]);
+	}
+
+	#[test]
+	fn japanese_accidentals_go_first() {
+		let names = spell_all(Table::Japanese, Accidentals::Words);
+		assert_eq!(names[6], ["変ロ", "ロ", "嬰ロ"]);
+		assert_eq!(names[0], ["変ハ", "ハ", "嬰ハ"]);
+	}
+
+	#[test]
+	fn accidentals_setting() {
+		assert_eq!(
+			spell_all(Table::English, Accidentals::Symbols)[2],
+			["E♭", "E", "E#"]
+		);
+		assert_eq!(
+			spell_all(Table::English, Accidentals::Words)[2],
+			["E flat", "E", "E sharp"]
+		);
+		// Tables with their own accidentals ignore it.
+		assert_eq!(
+			spell_all(Table::German, Accidentals::Words)[2],
+			["Es", "E", "Eis"]
+		);
+	}
+
+	#[test]
+	fn custom_tables() {
+		let rules = serde_json::from_str::<Rules>(
+			r#"{
+				"naturals": ["c", "d", "e", "f", "g", "a", "b"],
+				"sharp": "+",
+				"exceptions": {"e+": "f"}
+			}"#,
+		)
+		.unwrap();
+		let names = spell_all(Table::Custom(Box::new(rules)), Accidentals::Words);
+		assert_eq!(names[2], ["e flat", "e", "f"]);
+		assert_eq!(names[3], ["f flat", "f", "f+"]);
+	}
+
+	#[test]
+	fn words() {
+		assert_eq!(degree_with(3, -1, Accidentals::Symbols), "♭3");
+		assert_eq!(degree_with(3, -1, Accidentals::Words), "flat 3");
+		assert_eq!(degree_with(4, 1, Accidentals::Words), "sharp 4");
+		assert_eq!(degree_with(5, 0, Accidentals::Words), "5");
+		assert_eq!(symbols_with("m7♭5", Accidentals::Words), "m7 flat 5");
+		assert_eq!(
+			symbols_with("B♭ clarinet", Accidentals::Words),
+			"B flat clarinet"
+		);
+		assert_eq!(symbols_with("m7♭5", Accidentals::Symbols), "m7♭5");
+	}
+}
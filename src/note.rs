@@ -3,6 +3,16 @@ use std::sync::atomic::{AtomicU8, Ordering};
 
 use midly::MidiMessage;
 use nodi::{Event, Moment};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	chord::{self, Chord, ChordView},
+	drum::Drum,
+	key::Key,
+	names,
+	rhythm::Length,
+	tab,
+};
 
 static STYLE: AtomicU8 = AtomicU8::new(1);
 
@@ -18,73 +28,138 @@ pub fn style() -> NoteStyle {
 	NoteStyle::VALUES[STYLE.load(Ordering::Relaxed) as usize]
 }
 
-struct NoteName {
-	abc: &'static str,
-	doremi: &'static str,
-}
-
-const NOTES: [NoteName; 12] = [
-	NoteName {
-		abc: "C",
-		doremi: "Do",
-	},
-	NoteName {
-		abc: "C#",
-		doremi: "Do#",
-	},
-	NoteName {
-		abc: "D",
-		doremi: "Re",
-	},
-	NoteName {
-		abc: "E♭",
-		doremi: "Mi♭",
-	},
-	NoteName {
-		abc: "E",
-		doremi: "Mi",
-	},
-	NoteName {
-		abc: "F",
-		doremi: "Fa",
-	},
-	NoteName {
-		abc: "F#",
-		doremi: "Fa#",
-	},
-	NoteName {
-		abc: "G",
-		doremi: "Sol",
-	},
-	NoteName {
-		abc: "A♭",
-		doremi: "La♭",
-	},
-	NoteName {
-		abc: "A",
-		doremi: "La",
-	},
-	NoteName {
-		abc: "B♭",
-		doremi: "Si♭",
-	},
-	NoteName {
-		abc: "B",
-		doremi: "Si",
-	},
+pub fn set_style(style: NoteStyle) {
+	if let Some(i) = NoteStyle::VALUES.iter().position(|&s| s == style) {
+		STYLE.store(i as u8, Ordering::SeqCst);
+	}
+}
+
+/// Movable do syllables from the tonic of the major key, C through B in C
+/// major; minor keys are sung from La.
+const MAJOR_DO: [&str; 12] = [
+	"Do", "Ra", "Re", "Me", "Mi", "Fa", "Fi", "Sol", "Le", "La", "Te", "Ti",
+];
+/// Like `MAJOR_DO` but raising rather than lowering, as the minor mode does.
+const MINOR_DO: [&str; 12] = [
+	"Do", "Di", "Re", "Ri", "Mi", "Fa", "Fi", "Sol", "Si", "La", "Te", "Ti",
+];
+/// Scale degrees from the tonic, major or minor, with their accidentals.
+const DEGREES: [(u8, i8); 12] = [
+	(1, 0),
+	(2, -1),
+	(2, 0),
+	(3, -1),
+	(3, 0),
+	(4, 0),
+	(4, 1),
+	(5, 0),
+	(6, -1),
+	(6, 0),
+	(7, -1),
+	(7, 0),
 ];
+const INTERVALS: [&str; 12] = [
+	"",
+	"a half step",
+	"a step",
+	"a minor third",
+	"a major third",
+	"a fourth",
+	"a tritone",
+	"a fifth",
+	"a minor sixth",
+	"a major sixth",
+	"a minor seventh",
+	"a major seventh",
+];
+
+/// Returns the name of the pitch class `pc` relative to `key`, or C major if
+/// there is none, for the styles that name notes by their place in the key.
+fn relative_name(style: NoteStyle, pc: u8, key: Option<Key>) -> Option<String> {
+	let key = key.unwrap_or(Key {
+		sharps: 0,
+		minor: false,
+	});
+	match style {
+		NoteStyle::MovableDo => {
+			let major_tonic = (key.sharps as i32 * 7).rem_euclid(12);
+			let i = (pc as i32 - major_tonic).rem_euclid(12) as usize;
+			let name = if key.minor { MINOR_DO[i] } else { MAJOR_DO[i] };
+			Some(name.to_string())
+		}
+		NoteStyle::Degrees => {
+			let (n, acc) = DEGREES[(pc as i32 - key.tonic() as i32).rem_euclid(12) as usize];
+			Some(names::degree(n, acc))
+		}
+		_ => None,
+	}
+}
+
+/// Describes the step from the MIDI key `from` to `to`, such as "up a fifth".
+fn interval_name(from: u8, to: u8) -> String {
+	let d = to as i32 - from as i32;
+	if d == 0 {
+		return String::from("same note");
+	}
+	let dir = if d > 0 { "up" } else { "down" };
+	let (octaves, rem) = (d.abs() / 12, (d.abs() % 12) as usize);
+	match (octaves, rem) {
+		(0, _) => format!("{} {}", dir, INTERVALS[rem]),
+		(1, 0) => format!("{} an octave", dir),
+		(1, _) => format!("{} an octave and {}", dir, INTERVALS[rem]),
+		(_, 0) => format!("{} {} octaves", dir, octaves),
+		_ => format!("{} {} octaves and {}", dir, octaves, INTERVALS[rem]),
+	}
+}
+
+/// Returns the letter name of a note, such as "E♭".
+pub fn letter_name(letter: u8, acc: i8) -> String {
+	names::letter(letter, acc)
+}
+
+/// Returns the name of the pitch class `pc` in the current style, without an
+/// octave, for naming chords.
+pub fn pc_name(pc: u8, key: Option<Key>) -> String {
+	style().pc_name(pc, key)
+}
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Note {
-	offset: u8,
-	octave: u8,
+	pitch: u8,
+	/// The key the note is spelled in.
+	key: Option<Key>,
 }
 
 impl From<u8> for Note {
 	fn from(n: u8) -> Self {
-		let offset = n % 12;
-		let octave = if offset == 0 { n / 12 } else { n / 12 + 1 };
-		Self { offset, octave }
+		Self::new(n, None)
+	}
+}
+
+impl Note {
+	pub fn new(pitch: u8, key: Option<Key>) -> Self {
+		Self { pitch, key }
+	}
+
+	/// Returns the MIDI key of this note.
+	pub fn pitch(self) -> u8 {
+		self.pitch
+	}
+
+	pub fn key(self) -> Option<Key> {
+		self.key
+	}
+
+	fn offset(self) -> u8 {
+		self.pitch % 12
+	}
+
+	fn octave(self) -> u8 {
+		if self.offset() == 0 {
+			self.pitch / 12
+		} else {
+			self.pitch / 12 + 1
+		}
 	}
 }
 
@@ -94,11 +169,137 @@ impl fmt::Display for Note {
 	}
 }
 
-pub fn moment_notes(moment: &Moment, shift: i8) -> Option<Vec<Note>> {
+/// A note along with how long it is held.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TimedNote {
+	pub note: Note,
+	/// Empty if the note is never released.
+	pub length: Vec<Length>,
+	/// The MIDI key of the note heard before this one, for the interval style.
+	pub from: Option<u8>,
+}
+
+fn write_length(f: &mut fmt::Formatter, length: &[Length]) -> fmt::Result {
+	for (i, l) in length.iter().enumerate() {
+		if i == 0 {
+			write!(f, " {}", l)?;
+		} else {
+			write!(f, " tied to {}", l)?;
+		}
+	}
+	Ok(())
+}
+
+impl fmt::Display for TimedNote {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match (style(), self.from) {
+			(NoteStyle::Intervals, Some(from)) => {
+				f.write_str(&interval_name(from, self.note.pitch))?
+			}
+			_ => write!(f, "{}", self.note)?,
+		}
+		write_length(f, &self.length)
+	}
+}
+
+/// A single entry in the notes of a bar.
+pub enum Element {
+	Notes(Vec<TimedNote>),
+	/// Notes held over from the previous bar.
+	Tied(Vec<TimedNote>),
+	Rest(Vec<Length>),
+	Drums(Vec<Drum>),
+}
+
+impl fmt::Display for Element {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut first = true;
+		let mut sep = |f: &mut fmt::Formatter| {
+			if first {
+				first = false;
+				Ok(())
+			} else {
+				f.write_str(", ")
+			}
+		};
+
+		match self {
+			Self::Notes(notes) | Self::Tied(notes) => {
+				let view = chord::view();
+				let pitches = notes.iter().map(|n| n.note).collect::<Vec<_>>();
+				let chord = match (self, view) {
+					(Self::Tied(_), _) | (_, ChordView::Notes) => None,
+					_ => Chord::from_notes(&pitches),
+				};
+				if let Self::Tied(_) = self {
+					f.write_str("tied from previous bar: ")?;
+				}
+
+				if let Some(chord) = chord {
+					write!(f, "{}", chord)?;
+					if view == ChordView::Chords {
+						// Keep the length if every note shares it.
+						if notes.iter().all(|n| n.length == notes[0].length) {
+							for l in &notes[0].length {
+								write!(f, " {}", l)?;
+							}
+						}
+						return Ok(());
+					}
+					f.write_str(": ")?;
+				}
+
+				if let NoteStyle::Tab = style() {
+					for (n, pos) in notes.iter().zip(tab::fingering(&pitches)) {
+						sep(f)?;
+						match pos {
+							Ok(pos) => write!(f, "{}", pos)?,
+							Err(why) => write!(f, "{} ({})", n.note, why)?,
+						}
+						write_length(f, &n.length)?;
+					}
+					return Ok(());
+				}
+
+				for n in notes {
+					sep(f)?;
+					write!(f, "{}", n)?;
+				}
+			}
+			Self::Rest(lengths) => {
+				for l in lengths {
+					sep(f)?;
+					write!(f, "{} rest", l)?;
+				}
+			}
+			Self::Drums(drums) => {
+				for d in drums {
+					sep(f)?;
+					write!(f, "{}", d)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Returns the notes starting at `moment`.
+///
+/// The notes are spelled in `spelling`, and `length` is called with the channel and
+/// the untransposed key of every note to find out how long it is held.
+pub fn moment_notes<F>(
+	moment: &Moment,
+	shift: i8,
+	spelling: Option<Key>,
+	mut length: F,
+) -> Option<Vec<TimedNote>>
+where
+	F: FnMut(u8, u8) -> Vec<Length>,
+{
 	match &moment {
 		Moment::Empty => None,
 		Moment::Events(events) => {
-			let mut buf = Vec::new();
+			let mut buf = Vec::<TimedNote>::new();
 			for e in events {
 				if let Event::Midi(m) = e {
 					if m.channel == 9 {
@@ -107,11 +308,15 @@ pub fn moment_notes(moment: &Moment, shift: i8) -> Option<Vec<Note>> {
 					}
 					match m.message {
 						MidiMessage::NoteOn { key, vel } if vel > 0 => {
-							let key = key.as_int() as i32 + shift as i32;
-							if (0..=127).contains(&key) {
-								let k = Note::from(key as u8);
-								if !buf.contains(&k) {
-									buf.push(k);
+							let n = key.as_int() as i32 + shift as i32;
+							if (0..=127).contains(&n) {
+								let k = Note::new(n as u8, spelling);
+								if !buf.iter().any(|x| x.note == k) {
+									buf.push(TimedNote {
+										note: k,
+										length: length(m.channel.as_int(), key.as_int()),
+										from: None,
+									});
 								}
 							}
 						}
@@ -129,7 +334,7 @@ pub fn moment_notes(moment: &Moment, shift: i8) -> Option<Vec<Note>> {
 	}
 }
 
-#[derive(Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 pub enum NoteStyle {
 	/// A, B, C
 	Abc,
@@ -139,19 +344,101 @@ pub enum NoteStyle {
 	Doremi,
 	/// Do 2, Re 5, Mi 7
 	DoremiN,
+	/// String 3 fret 2
+	Tab,
+	/// Do, Re, Mi with Do on the tonic of the key
+	MovableDo,
+	/// 1, ♭3, 5 from the tonic of the key
+	Degrees,
+	/// Up a major third, down a step
+	Intervals,
 }
 
 impl NoteStyle {
-	pub const VALUES: [Self; 4] = [Self::Abc, Self::AbcN, Self::Doremi, Self::DoremiN];
+	pub const VALUES: [Self; 8] = [
+		Self::Abc,
+		Self::AbcN,
+		Self::Doremi,
+		Self::DoremiN,
+		Self::Tab,
+		Self::MovableDo,
+		Self::Degrees,
+		Self::Intervals,
+	];
+
+	/// Returns the name of the pitch class `pc` in this style, without an
+	/// octave, for naming chords.
+	///
+	/// Scale degrees are left to letter names, as a number followed by a chord
+	/// quality cannot be read.
+	pub fn pc_name(self, pc: u8, key: Option<Key>) -> String {
+		if self == Self::MovableDo {
+			if let Some(name) = relative_name(self, pc, key) {
+				return name;
+			}
+		}
+		let (letter, acc) = Key::spell(key, pc);
+		match self {
+			Self::Abc
+			| Self::AbcN
+			| Self::Tab
+			| Self::MovableDo
+			| Self::Degrees
+			| Self::Intervals => names::letter(letter, acc),
+			Self::Doremi | Self::DoremiN => names::syllable(letter, acc),
+		}
+	}
 
 	#[inline]
 	pub fn display_note(self, n: Note, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		let display = &NOTES[n.offset as usize];
+		if let Some(name) = relative_name(self, n.offset(), n.key) {
+			return f.write_str(&name);
+		}
+		let (letter, acc) = Key::spell(n.key, n.offset());
+		// Keep the octave apart from names spelled out in words.
+		let with_octave = |name: String| {
+			let sep = if name.contains(' ') { " " } else { "" };
+			format!("{}{}{}", name, sep, n.octave())
+		};
 		match self {
-			Self::Abc => f.write_str(display.abc),
-			Self::AbcN => write!(f, "{}{}", display.abc, n.octave),
-			Self::Doremi => f.write_str(display.doremi),
-			Self::DoremiN => write!(f, "{}{}", display.doremi, n.octave),
+			Self::Abc => f.write_str(&names::letter(letter, acc)),
+			// Notes are only placed on the fretboard in groups, see `Element`.
+			// Intervals need the note before, see `TimedNote`.
+			Self::AbcN | Self::Tab | Self::MovableDo | Self::Degrees | Self::Intervals => {
+				f.write_str(&with_octave(names::letter(letter, acc)))
+			}
+			Self::Doremi => f.write_str(&names::syllable(letter, acc)),
+			Self::DoremiN => f.write_str(&with_octave(names::syllable(letter, acc))),
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Shows a note in `style`, leaving the current style alone.
+	struct Styled(NoteStyle, Note);
+
+	impl fmt::Display for Styled {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			self.0.display_note(self.1, f)
+		}
+	}
+
+	#[test]
+	fn chords_keep_letter_names_with_scale_degrees() {
+		let c_major = Some(Key {
+			sharps: 0,
+			minor: false,
+		});
+		assert_eq!(
+			Styled(NoteStyle::Degrees, Note::new(70, c_major)).to_string(),
+			"♭7"
+		);
+		assert_eq!(NoteStyle::Degrees.pc_name(7, c_major), "G");
+		assert_eq!(NoteStyle::Degrees.pc_name(11, c_major), "B");
+		assert_eq!(NoteStyle::MovableDo.pc_name(7, c_major), "Sol");
+		assert_eq!(NoteStyle::MovableDo.pc_name(11, c_major), "Ti");
+	}
+}
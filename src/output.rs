@@ -0,0 +1,57 @@
+use midir::MidiOutputConnection;
+use midly::{live::LiveEvent, MidiMessage};
+
+/// A MIDI output that keeps track of the notes it has started.
+pub struct Output {
+	con: MidiOutputConnection,
+	/// The keys sounding on each channel, one bit per key.
+	sounding: [u128; 16],
+}
+
+impl Output {
+	pub fn new(con: MidiOutputConnection) -> Self {
+		Self {
+			con,
+			sounding: [0; 16],
+		}
+	}
+
+	pub fn send(&mut self, msg: &[u8]) {
+		if let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(msg) {
+			let sounding = &mut self.sounding[channel.as_int() as usize];
+			match message {
+				MidiMessage::NoteOn { key, vel } if vel > 0 => *sounding |= 1 << key.as_int(),
+				MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+					*sounding &= !(1 << key.as_int())
+				}
+				_ => (),
+			}
+		}
+		let _ = self.con.send(msg);
+	}
+
+	/// Sends a NoteOff for every note started through `self` that is still
+	/// sounding.
+	pub fn release(&mut self) {
+		for ch in 0..16_u8 {
+			let mut sounding = std::mem::take(&mut self.sounding[ch as usize]);
+			while sounding != 0 {
+				let key = sounding.trailing_zeros() as u8;
+				sounding &= sounding - 1;
+				let _ = self.con.send(&[0x80 | ch, key, 0]);
+			}
+		}
+	}
+
+	/// Stops every note: the ones started through `self` explicitly, then all
+	/// the others with All Notes Off and All Sound Off on every channel. The
+	/// sustain pedal is released as well.
+	pub fn panic(&mut self) {
+		self.release();
+		for ch in 0..16_u8 {
+			let _ = self.con.send(&[0xb0 | ch, 64, 0]);
+			let _ = self.con.send(&[0xb0 | ch, 123, 0]);
+			let _ = self.con.send(&[0xb0 | ch, 120, 0]);
+		}
+	}
+}
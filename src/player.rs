@@ -1,38 +1,86 @@
 use std::{
+	borrow::Cow,
 	sync::{
+		atomic::{AtomicU32, AtomicUsize, Ordering},
 		mpsc::{self, Receiver, Sender, SyncSender},
 		Arc, Mutex,
 	},
 	thread,
+	time::Duration,
 };
 
 use midir::MidiOutputConnection;
-use nodi::{Event, Moment, Timer};
+use nodi::{timers::Ticker, Event, Moment, Timer};
 
 use crate::{
+	analysis::Analysis,
 	bar::Bar,
-	note::{self, moment_notes},
-	Command, Response, State,
+	chord,
+	config::Practice,
+	data::Store,
+	init::Selection,
+	metronome::{self, Metronome},
+	note,
+	output::Output,
+	section::{self, Section},
+	session::Session,
+	Command, Jump, Notes, Response, State, Target,
 };
 
 type Bars = Vec<Bar>;
 
+/// How [Player::follow] plays its range.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+	Once,
+	Loop,
+	/// Loop, raising the speed as the repetitions add up.
+	Trainer,
+}
+
 pub struct Player {
 	output: Sender<Response>,
-	con: Arc<Mutex<MidiOutputConnection>>,
+	con: Arc<Mutex<Output>>,
 	index: usize,
 	last_forward: bool,
 	all: Arc<Bars>,
 	solo: Arc<Bars>,
 	solo_on: bool,
+	/// Whether the solo part is the drum channel.
+	drums: bool,
 	n_bars: usize,
 	transpose: i8,
+	/// What the transposition was chosen for, if it was.
+	target: Option<Target>,
 	speed: f32,
+	/// The bar being played by "play from here" or a loop, if one is running.
+	following: Option<Arc<AtomicUsize>>,
+	/// The speed the tempo trainer is at, stored as `f32` bits.
+	trained_speed: Option<Arc<AtomicU32>>,
+	loop_start: Option<usize>,
+	loop_end: Option<usize>,
+	practice: Practice,
+	metronome: Metronome,
+	/// Markers from the file and bookmarks, sorted by bar.
+	sections: Arc<Vec<Section>>,
+	/// Where bookmarks are saved, if anywhere.
+	bookmarks: Option<Store>,
+	/// The keys the song seems to be in.
+	analysis: Analysis,
+	/// Where the session is saved on quit, and the part of the song followed.
+	session: Option<(Store, Selection)>,
 }
 
 impl Player {
-	pub fn new(con: MidiOutputConnection, output: Sender<Response>, all: Bars, solo: Bars) -> Self {
-		let con = Arc::new(Mutex::new(con));
+	pub fn new(
+		con: MidiOutputConnection,
+		output: Sender<Response>,
+		all: Bars,
+		solo: Bars,
+		drums: bool,
+		practice: Practice,
+	) -> Self {
+		let con = Arc::new(Mutex::new(Output::new(con)));
 		let n_bars = all.len();
 
 		let all = Arc::new(all);
@@ -45,19 +93,93 @@ impl Player {
 			all,
 			solo,
 			solo_on: false,
+			drums,
 			last_forward: true,
 			transpose: 0,
+			target: None,
 			speed: 1.0,
+			following: None,
+			trained_speed: None,
+			loop_start: None,
+			loop_end: None,
+			practice,
+			metronome: Metronome::Off,
+			sections: Arc::new(Vec::new()),
+			bookmarks: None,
+			session: None,
+			analysis: Analysis::default(),
+		}
+	}
+
+	/// Sets the keys shown by the info command.
+	pub(crate) fn set_analysis(&mut self, analysis: Analysis) {
+		self.analysis = analysis;
+	}
+
+	/// Picks up where `session` left off.
+	pub(crate) fn restore(&mut self, session: &Session) {
+		if session.bar < self.n_bars {
+			self.index = session.bar + 1;
+			self.last_forward = true;
+		}
+		self.transpose = session.transposition.clamp(-48, 48);
+		self.speed = session.speed.max(0.1);
+		self.solo_on = session.solo;
+		note::set_style(session.note_style);
+	}
+
+	/// Sets where the session is saved when the player quits.
+	pub(crate) fn keep_session(&mut self, store: Option<Store>, selection: Selection) {
+		self.session = store.map(|s| (s, selection));
+	}
+
+	fn save_session(&self, bar: usize) {
+		if let Some((store, selection)) = &self.session {
+			let session = Session {
+				bar,
+				transposition: self.transpose,
+				speed: self.speed,
+				solo: self.solo_on,
+				selection: selection.clone(),
+				note_style: note::style(),
+			};
+			// There is no one left to tell if this fails.
+			let _ = store.save(&session);
 		}
 	}
 
+	/// Sets the markers found in the file, adding the bookmarks saved in `store`.
+	pub(crate) fn set_sections(&mut self, markers: Vec<Section>, store: Option<Store>) {
+		let mut sections = markers;
+		if let Some(saved) = store.as_ref().and_then(|s| s.load::<Vec<Section>>()) {
+			sections.extend(saved.into_iter().map(|s| Section {
+				bookmark: true,
+				..s
+			}));
+		}
+		sections.sort_by_key(|s| s.bar);
+		self.sections = Arc::new(sections);
+		self.bookmarks = store;
+	}
+
 	pub fn start(mut self, commands: Receiver<Command>) {
 		let mut last_sender: Option<SyncSender<_>> = None;
-		let mut last_played = 0_usize;
+		// After a restored session, the bar left off at.
+		let mut last_played = self.current_bar();
 		for c in &commands {
 			if let Some(ch) = &last_sender {
 				ch.send(true).ok();
 			}
+			// Stay on the bar continuous playback stopped at.
+			if let Some(pos) = self.following.take() {
+				last_played = pos.load(Ordering::SeqCst);
+				self.index = last_played + 1;
+				self.last_forward = true;
+			}
+			// Keep the speed the tempo trainer got up to.
+			if let Some(speed) = self.trained_speed.take() {
+				self.speed = f32::from_bits(speed.load(Ordering::SeqCst));
+			}
 			let (cancel_send, cancel) = mpsc::sync_channel(0);
 			last_sender = Some(cancel_send);
 
@@ -77,6 +199,26 @@ impl Player {
 					}
 				}
 				Command::Replay => self.play(last_played, cancel),
+				Command::PlayFromHere => {
+					let end = self.n_bars.saturating_sub(1);
+					self.follow(last_played, end, Mode::Once, cancel);
+				}
+				Command::LoopStart => {
+					self.loop_start = Some(last_played);
+					self.state(last_played);
+				}
+				Command::LoopEnd => {
+					self.loop_end = Some(last_played);
+					self.state(last_played);
+				}
+				Command::Loop => {
+					let (start, end) = self.loop_range(last_played);
+					self.follow(start, end, Mode::Loop, cancel);
+				}
+				Command::Trainer => {
+					let (start, end) = self.loop_range(last_played);
+					self.follow(start, end, Mode::Trainer, cancel);
+				}
 				Command::Silence => self.silence(),
 				Command::Reset => {
 					self.reset();
@@ -87,7 +229,21 @@ impl Player {
 					self.transpose(n);
 					self.state(last_played);
 				}
-				Command::Info => self.state(last_played),
+				Command::TransposeTo(target) => match self.transpose_to(target, last_played) {
+					Ok(_) => self.state(last_played),
+					Err(e) => self
+						.output
+						.send(Response::Message(format!(
+							"Cannot transpose {}: {}.",
+							target, e
+						)))
+						.unwrap(),
+				},
+				Command::Info => {
+					let mut state = self.state_at(last_played);
+					state.analysis = Some(self.analysis.transpose(self.transpose));
+					self.output.send(Response::State(state)).unwrap();
+				}
 				Command::Speed(f) => {
 					self.change_speed(f);
 					self.state(last_played);
@@ -95,6 +251,58 @@ impl Player {
 				Command::NoteStyle => {
 					note::toggle_style();
 				}
+				Command::ChordView => {
+					chord::toggle_view();
+				}
+				Command::Jump(jump) => {
+					let last = self.n_bars.saturating_sub(1);
+					let n = match jump {
+						Jump::To(n) => n,
+						Jump::First => 0,
+						Jump::Last => last,
+						Jump::By(d) => last_played.saturating_add_signed(d).min(last),
+					};
+					if n < self.n_bars {
+						self.jump(n, cancel);
+						last_played = n;
+					} else {
+						self.output.send(Response::EndOfTrack).unwrap();
+					}
+				}
+				Command::NextSection => match section::next(&self.sections, last_played) {
+					Some(s) => {
+						last_played = s.bar;
+						self.jump(s.bar, cancel);
+					}
+					None => self.output.send(Response::EndOfTrack).unwrap(),
+				},
+				Command::PrevSection => match section::prev(&self.sections, last_played) {
+					Some(s) => {
+						last_played = s.bar;
+						self.jump(s.bar, cancel);
+					}
+					None => self.output.send(Response::StartOfTrack).unwrap(),
+				},
+				Command::GoToSection(name) => match section::find(&self.sections, &name) {
+					Some(s) => {
+						last_played = s.bar;
+						self.jump(s.bar, cancel);
+					}
+					None => self
+						.output
+						.send(Response::Message(format!("No section named {}.", name)))
+						.unwrap(),
+				},
+				Command::Bookmark(name) => self.bookmark(last_played, name),
+				Command::Quit => {
+					self.silence();
+					self.save_session(last_played);
+					break;
+				}
+				Command::Metronome => {
+					self.metronome = self.metronome.next();
+					self.state(last_played);
+				}
 			};
 		}
 	}
@@ -127,6 +335,49 @@ impl Player {
 		Some(self.index)
 	}
 
+	/// Plays the `n`th bar, continuing from it on the next and previous commands.
+	fn jump(&mut self, n: usize, cancel: Receiver<bool>) {
+		self.index = n + 1;
+		self.last_forward = true;
+		self.play(n, cancel);
+	}
+
+	/// Bookmarks the `n`th bar, or removes its bookmarks if `name` is empty.
+	fn bookmark(&mut self, n: usize, name: String) {
+		let mut sections = self.sections.to_vec();
+		let name = name.trim().to_string();
+		let msg = if name.is_empty() {
+			sections.retain(|s| !(s.bookmark && s.bar == n));
+			format!("Removed the bookmarks on bar {}.", n + 1)
+		} else {
+			let msg = format!("Bookmarked bar {} as {}.", n + 1, name);
+			let i = sections.partition_point(|s| s.bar <= n);
+			sections.insert(
+				i,
+				Section {
+					bar: n,
+					name,
+					bookmark: true,
+				},
+			);
+			msg
+		};
+
+		let saved = match &self.bookmarks {
+			Some(store) => {
+				let bookmarks = sections.iter().filter(|s| s.bookmark).collect::<Vec<_>>();
+				store.save(&bookmarks).map_err(|e| e.to_string())
+			}
+			None => Err(String::from("there is no data directory")),
+		};
+		self.sections = Arc::new(sections);
+		let msg = match saved {
+			Ok(_) => msg,
+			Err(e) => format!("{}\nThe bookmarks could not be saved: {}", msg, e),
+		};
+		self.output.send(Response::Message(msg)).unwrap();
+	}
+
 	fn reset(&mut self) {
 		self.index = 0;
 		self.last_forward = true;
@@ -134,19 +385,13 @@ impl Player {
 	}
 
 	fn silence(&self) {
-		let mut con = self.con.lock().unwrap();
-		let _ = con.send(&[0xb0, 123]);
-		let _ = con.send(&[0xb0, 120]);
+		self.con.lock().unwrap().panic();
 	}
 
 	fn play(&self, n: usize, cancel: Receiver<bool>) {
 		self.silence();
 		let con = Arc::clone(&self.con);
-		let notes = self.solo[n]
-			.moments
-			.iter()
-			.filter_map(|m| moment_notes(m, self.transpose))
-			.collect::<Vec<_>>();
+		let notes = bar_notes(&self.solo, &self.sections, n, self.drums, self.transpose);
 		self.output.send(Response::Notes(notes)).unwrap();
 
 		let bars = if self.solo_on {
@@ -157,65 +402,218 @@ impl Player {
 
 		let speed = self.speed;
 		let transpose = self.transpose;
+		let metronome = self.metronome;
 
 		thread::spawn(move || {
-			let mut buf = Vec::new();
-
-			let mut counter = 0_u32;
 			let mut con = con.lock().unwrap();
+			chase(&mut con, &bars[n]);
 			let mut timer = bars[n].timer;
+			let mut counter = 0;
+			if metronome == Metronome::CountIn
+				&& !play_count_in(&mut con, &bars[n], &mut timer, &mut counter, speed, &cancel)
+			{
+				return;
+			}
+			let moments = if metronome == Metronome::Off {
+				bars[n].transposed_moments(transpose)
+			} else {
+				// Clicks need the whole bar to line up with the beats.
+				with_clicks(&bars[n], transpose, true)
+			};
+			play_moments(&mut con, &moments, &mut timer, &mut counter, speed, &cancel);
+		});
+	}
+
+	/// Returns the first and the last bar of the loop, defaulting to `current`
+	/// for unmarked ends.
+	fn loop_range(&self, current: usize) -> (usize, usize) {
+		let start = self.loop_start.unwrap_or(current);
+		let end = self.loop_end.unwrap_or(current);
+		(start.min(end), start.max(end))
+	}
 
-			for moment in bars[n].transposed_moments(transpose).iter() {
-				if cancel.try_recv().is_ok() {
+	/// Plays the bars from `start` to `end` until cancelled, announcing each bar
+	/// as it starts.
+	fn follow(&mut self, start: usize, end: usize, mode: Mode, cancel: Receiver<bool>) {
+		let bars = if self.solo_on {
+			Arc::clone(&self.solo)
+		} else {
+			Arc::clone(&self.all)
+		};
+		if start >= bars.len() {
+			self.output.send(Response::EndOfTrack).unwrap();
+			return;
+		}
+		let end = end.min(bars.len() - 1);
+
+		self.silence();
+		let con = Arc::clone(&self.con);
+		let output = self.output.clone();
+		let solo = Arc::clone(&self.solo);
+		let sections = Arc::clone(&self.sections);
+
+		let pos = Arc::new(AtomicUsize::new(start));
+		self.following = Some(Arc::clone(&pos));
+		let (transpose, drums, metronome) = (self.transpose, self.drums, self.metronome);
+		let practice = self.practice;
+		let pause = Duration::from_secs_f32(practice.loop_pause.max(0.0));
+
+		let mut speed = self.speed;
+		let mut state = None;
+		if mode == Mode::Trainer {
+			speed = practice.trainer_start.clamp(0.1, 1.0);
+			let shared = Arc::new(AtomicU32::new(speed.to_bits()));
+			self.trained_speed = Some(Arc::clone(&shared));
+			let mut s = self.state_at(start);
+			s.speed = speed;
+			self.output.send(Response::State(s.clone())).unwrap();
+			state = Some((s, shared));
+		}
+
+		thread::spawn(move || {
+			let mut con = con.lock().unwrap();
+			// Repetitions since the last speed change.
+			let mut repetitions = 0_u32;
+			let mut count_in = metronome == Metronome::CountIn;
+			loop {
+				chase(&mut con, &bars[start]);
+				let mut timer = bars[start].timer;
+				let mut counter = 0;
+				if count_in {
+					count_in = false;
+					if !play_count_in(
+						&mut con,
+						&bars[start],
+						&mut timer,
+						&mut counter,
+						speed,
+						&cancel,
+					) {
+						return;
+					}
+				}
+
+				for n in start..=end {
+					// Wait out the end of the previous bar before announcing this one.
+					if !sleep_ticks(&timer, &mut counter, speed, &cancel) {
+						con.panic();
+						return;
+					}
+					pos.store(n, Ordering::SeqCst);
+					if n < solo.len() {
+						output
+							.send(Response::Notes(bar_notes(
+								&solo, &sections, n, drums, transpose,
+							)))
+							.unwrap();
+					}
+					// Only the first bar lacks the notes struck before it.
+					let ties = n == start;
+					let moments = if metronome == Metronome::Off {
+						bars[n].transposed_bar(transpose, ties)
+					} else {
+						with_clicks(&bars[n], transpose, ties)
+					};
+					if !play_moments(&mut con, &moments, &mut timer, &mut counter, speed, &cancel) {
+						return;
+					}
+				}
+
+				if mode == Mode::Once {
+					break;
+				}
+				if !sleep_ticks(&timer, &mut counter, speed, &cancel) {
+					con.panic();
 					return;
 				}
+				// Notes struck in the last bar may be released after it, which
+				// is never played.
+				con.release();
 
-				match moment {
-					Moment::Events(events) if !events.is_empty() => {
-						let dur = timer.sleep_duration(counter).div_f64(speed as f64);
-						if !dur.is_zero() {
-							thread::sleep(dur);
-						}
-						counter = 0;
-						for event in events {
-							match event {
-								Event::Tempo(val) => timer.change_tempo(*val),
-								Event::Midi(msg) => {
-									buf.clear();
-									let _ = msg.write(&mut buf);
-									let _ = con.send(&buf);
-								}
-								_ => (),
-							};
-						}
+				repetitions += 1;
+				if let Some((s, shared)) = &mut state {
+					if speed < 1.0 && repetitions >= practice.trainer_repetitions {
+						repetitions = 0;
+						speed = (speed + practice.trainer_step.max(0.01)).min(1.0);
+						shared.store(speed.to_bits(), Ordering::SeqCst);
+						s.speed = speed;
+						output.send(Response::State(s.clone())).unwrap();
 					}
-					_ => (),
-				};
-				counter += 1;
+				}
+				if cancel.recv_timeout(pause).is_ok() {
+					con.panic();
+					return;
+				}
+			}
+
+			if end + 1 == bars.len() {
+				output.send(Response::EndOfTrack).unwrap();
 			}
 		});
 	}
 
 	fn transpose(&mut self, n: i8) {
+		self.target = None;
 		if n == 0 {
 			self.transpose = 0;
 		} else {
-			self.transpose = (self.transpose + n) % 12;
+			self.transpose = self.transpose.saturating_add(n).clamp(-48, 48);
 		}
 	}
 
+	/// Sets the transposition that reaches `target` from the key of the `bar`th
+	/// bar.
+	pub fn transpose_to(&mut self, target: Target, bar: usize) -> Result<(), &'static str> {
+		let key = self
+			.solo
+			.get(bar)
+			.or_else(|| self.all.get(bar))
+			.and_then(|b| b.key.or(b.estimated_key));
+		self.transpose = target.transposition(key)?;
+		self.target = Some(target);
+		Ok(())
+	}
+
+	/// Returns the bar playback continues from.
+	pub fn current_bar(&self) -> usize {
+		self.index.saturating_sub(1)
+	}
+
 	fn state(&self, index: usize) {
 		self.output
-			.send(Response::State(State {
-				transposition: self.transpose,
-				index,
-				solo: self.solo_on,
-				length: self.solo.len(),
-				speed: self.speed,
-			}))
+			.send(Response::State(self.state_at(index)))
 			.unwrap();
 	}
 
+	fn state_at(&self, index: usize) -> State {
+		State {
+			transposition: self.transpose,
+			target: self.target,
+			index,
+			solo: self.solo_on,
+			length: self.solo.len(),
+			speed: self.speed,
+			time_sig: self.solo.get(index).map(|b| b.time_sig).unwrap_or_default(),
+			key: self
+				.solo
+				.get(index)
+				.and_then(|b| b.key)
+				.map(|k| k.transpose(self.transpose)),
+			estimated_key: self
+				.all
+				.get(index)
+				.and_then(|b| b.estimated_key)
+				.map(|k| k.transpose(self.transpose)),
+			analysis: None,
+			loop_range: if self.loop_start.is_some() || self.loop_end.is_some() {
+				Some(self.loop_range(index))
+			} else {
+				None
+			},
+			metronome: self.metronome,
+		}
+	}
+
 	fn change_speed(&mut self, f: f32) {
 		let f = f + self.speed;
 		if f < 0.1 {
@@ -225,3 +623,109 @@ impl Player {
 		}
 	}
 }
+
+/// Returns the notes of the `n`th bar, announcing the sections starting there
+/// and the time signature if it changed.
+fn bar_notes(bars: &[Bar], sections: &[Section], n: usize, drums: bool, transpose: i8) -> Notes {
+	let mut notes = if drums {
+		bars[n].drums()
+	} else {
+		bars[n].notes(transpose)
+	};
+	if n == 0 || bars[n - 1].time_sig != bars[n].time_sig {
+		notes.time_sig = Some(bars[n].time_sig);
+	}
+	notes.sections = section::names_at(sections, n);
+	notes
+}
+
+/// Sends the programs, controllers and pitch bends in effect at the start of
+/// `bar`, so that it sounds the same wherever playback starts.
+fn chase(con: &mut Output, bar: &Bar) {
+	let mut buf = Vec::new();
+	for e in &bar.chase {
+		buf.clear();
+		let _ = e.write(&mut buf);
+		con.send(&buf);
+	}
+}
+
+/// Returns the whole of `bar` with metronome clicks added.
+fn with_clicks(bar: &Bar, transpose: i8, ties: bool) -> Cow<'_, [Moment]> {
+	let mut moments = bar.transposed_bar(transpose, ties);
+	metronome::add_clicks(moments.to_mut(), bar.time_sig, bar.tpb);
+	moments
+}
+
+/// Plays a bar of clicks in the meter and tempo of `bar`, returning `false` if
+/// cancelled.
+fn play_count_in(
+	con: &mut Output,
+	bar: &Bar,
+	timer: &mut Ticker,
+	counter: &mut u32,
+	speed: f32,
+	cancel: &Receiver<bool>,
+) -> bool {
+	let moments = metronome::count_in(bar.time_sig, bar.tpb);
+	play_moments(con, &moments, timer, counter, speed, cancel)
+}
+
+/// Sleeps for `counter` ticks and resets it, returning `false` if cancelled
+/// meanwhile.
+fn sleep_ticks(timer: &Ticker, counter: &mut u32, speed: f32, cancel: &Receiver<bool>) -> bool {
+	if cancel.try_recv().is_ok() {
+		return false;
+	}
+	let dur = timer.sleep_duration(*counter).div_f64(speed as f64);
+	if !dur.is_zero() {
+		thread::sleep(dur);
+	}
+	*counter = 0;
+	true
+}
+
+/// Plays `moments`, returning `false` if cancelled. Notes still sounding are
+/// stopped on cancel.
+///
+/// `counter` holds the ticks passed since the last event, so that playback
+/// can carry on seamlessly between calls.
+fn play_moments(
+	con: &mut Output,
+	moments: &[Moment],
+	timer: &mut Ticker,
+	counter: &mut u32,
+	speed: f32,
+	cancel: &Receiver<bool>,
+) -> bool {
+	let mut buf = Vec::new();
+	for moment in moments {
+		if cancel.try_recv().is_ok() {
+			con.panic();
+			return false;
+		}
+
+		match moment {
+			Moment::Events(events) if !events.is_empty() => {
+				if !sleep_ticks(timer, counter, speed, cancel) {
+					con.panic();
+					return false;
+				}
+				for event in events {
+					match event {
+						Event::Tempo(val) => timer.change_tempo(*val),
+						Event::Midi(msg) => {
+							buf.clear();
+							let _ = msg.write(&mut buf);
+							con.send(&buf);
+						}
+						_ => (),
+					};
+				}
+			}
+			_ => (),
+		};
+		*counter += 1;
+	}
+	true
+}
@@ -0,0 +1,305 @@
+use std::fmt;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Value {
+	Whole,
+	Half,
+	Quarter,
+	Eighth,
+	Sixteenth,
+	ThirtySecond,
+}
+
+impl Value {
+	const VALUES: [Self; 6] = [
+		Self::Whole,
+		Self::Half,
+		Self::Quarter,
+		Self::Eighth,
+		Self::Sixteenth,
+		Self::ThirtySecond,
+	];
+
+	/// Length of this value, in quarter notes.
+	fn quarters(self) -> f64 {
+		match self {
+			Self::Whole => 4.0,
+			Self::Half => 2.0,
+			Self::Quarter => 1.0,
+			Self::Eighth => 0.5,
+			Self::Sixteenth => 0.25,
+			Self::ThirtySecond => 0.125,
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Self::Whole => "whole",
+			Self::Half => "half",
+			Self::Quarter => "quarter",
+			Self::Eighth => "eighth",
+			Self::Sixteenth => "sixteenth",
+			Self::ThirtySecond => "thirty-second",
+		}
+	}
+}
+
+/// A note value such as a dotted quarter or an eighth triplet.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Length {
+	value: Value,
+	dotted: bool,
+	triplet: bool,
+}
+
+impl Length {
+	fn quarters(self) -> f64 {
+		let q = self.value.quarters();
+		if self.dotted {
+			q * 1.5
+		} else if self.triplet {
+			q * 2.0 / 3.0
+		} else {
+			q
+		}
+	}
+
+	fn candidates() -> impl Iterator<Item = Self> {
+		static MODIFIERS: [(bool, bool); 3] = [(false, false), (true, false), (false, true)];
+		Value::VALUES.iter().flat_map(|&value| {
+			MODIFIERS.iter().map(move |&(dotted, triplet)| Self {
+				value,
+				dotted,
+				triplet,
+			})
+		})
+	}
+
+	/// Returns the note value closest to `ticks`.
+	///
+	/// Lengths longer than a dotted whole note are expressed as whole notes
+	/// tied to the remainder.
+	pub fn approximate(ticks: u32, tpb: u16) -> Vec<Self> {
+		let mut quarters = ticks as f64 / tpb as f64;
+		let mut buf = Vec::new();
+		while quarters > 6.0 {
+			buf.push(Self {
+				value: Value::Whole,
+				dotted: false,
+				triplet: false,
+			});
+			quarters -= 4.0;
+		}
+
+		if quarters >= Value::ThirtySecond.quarters() / 2.0 {
+			let best = Self::candidates()
+				.min_by(|a, b| {
+					let da = (a.quarters() - quarters).abs();
+					let db = (b.quarters() - quarters).abs();
+					da.partial_cmp(&db).unwrap()
+				})
+				.unwrap();
+			buf.push(best);
+		}
+
+		buf
+	}
+
+	/// Splits `ticks` into as few plain or dotted values as possible.
+	///
+	/// Gaps too short to be written as a thirty-second are ignored.
+	pub fn split(ticks: u32, tpb: u16) -> Vec<Self> {
+		let shortest = Value::ThirtySecond.quarters();
+		let mut quarters = ticks as f64 / tpb as f64;
+		let mut buf = Vec::new();
+
+		while quarters >= shortest / 2.0 {
+			let next = Self::candidates()
+				.filter(|l| !l.triplet && l.quarters() <= quarters + shortest / 4.0)
+				.max_by(|a, b| a.quarters().partial_cmp(&b.quarters()).unwrap());
+			match next {
+				Some(l) => {
+					buf.push(l);
+					quarters -= l.quarters();
+				}
+				None => break,
+			}
+		}
+
+		buf
+	}
+}
+
+impl fmt::Display for Length {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.dotted {
+			write!(f, "dotted {}", self.value.name())
+		} else if self.triplet {
+			write!(f, "{} triplet", self.value.name())
+		} else {
+			f.write_str(self.value.name())
+		}
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct TimeSignature {
+	pub numerator: u8,
+	/// The beat unit, as a negative power of 2 (2 for quarter notes).
+	pub denominator: u8,
+}
+
+impl Default for TimeSignature {
+	fn default() -> Self {
+		Self {
+			numerator: 4,
+			denominator: 2,
+		}
+	}
+}
+
+impl TimeSignature {
+	/// Returns how many ticks a single beat lasts.
+	pub fn beat_ticks(self, tpb: u16) -> f64 {
+		tpb as f64 * 4.0 / 2_f64.powi(self.denominator as i32)
+	}
+}
+
+impl fmt::Display for TimeSignature {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}/{}",
+			self.numerator,
+			2_u32.saturating_pow(self.denominator as u32)
+		)
+	}
+}
+
+/// Subdivisions of a beat, counted as "1 e and a" or "1 trip let".
+const SUBDIVISIONS: [(f64, &str); 7] = [
+	(0.0, ""),
+	(0.25, "e"),
+	(1.0 / 3.0, " trip"),
+	(0.5, " and"),
+	(2.0 / 3.0, " let"),
+	(0.75, "a"),
+	(1.0, ""),
+];
+
+/// The position of a note within a bar.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Beat {
+	beat: u32,
+	sub: &'static str,
+}
+
+impl Beat {
+	/// Finds the beat `offset` ticks into a bar falls on, snapping it to the
+	/// nearest sixteenth or triplet.
+	pub fn new(offset: usize, tpb: u16, time_sig: TimeSignature) -> Self {
+		let pos = offset as f64 / time_sig.beat_ticks(tpb);
+		let beat = pos.floor();
+		let frac = pos - beat;
+		let (i, _) = SUBDIVISIONS
+			.iter()
+			.enumerate()
+			.min_by(|(_, a), (_, b)| (a.0 - frac).abs().partial_cmp(&(b.0 - frac).abs()).unwrap())
+			.unwrap();
+
+		if i == SUBDIVISIONS.len() - 1 {
+			Self {
+				beat: beat as u32 + 2,
+				sub: "",
+			}
+		} else {
+			Self {
+				beat: beat as u32 + 1,
+				sub: SUBDIVISIONS[i].1,
+			}
+		}
+	}
+}
+
+impl fmt::Display for Beat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "beat {}{}", self.beat, self.sub)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TPB: u16 = 96;
+
+	fn names(lengths: &[Length]) -> Vec<String> {
+		lengths.iter().map(|l| l.to_string()).collect()
+	}
+
+	#[test]
+	fn approximate_snaps_to_the_nearest_value() {
+		assert_eq!(names(&Length::approximate(96, TPB)), ["quarter"]);
+		assert_eq!(names(&Length::approximate(144, TPB)), ["dotted quarter"]);
+		assert_eq!(names(&Length::approximate(32, TPB)), ["eighth triplet"]);
+		assert_eq!(names(&Length::approximate(50, TPB)), ["eighth"]);
+	}
+
+	#[test]
+	fn approximate_ties_long_notes() {
+		assert_eq!(
+			names(&Length::approximate(96 * 10, TPB)),
+			["whole", "dotted whole"]
+		);
+		assert_eq!(
+			names(&Length::approximate(96 * 11, TPB)),
+			["whole", "whole", "dotted half"]
+		);
+		assert_eq!(names(&Length::approximate(96 * 6, TPB)), ["dotted whole"]);
+	}
+
+	#[test]
+	fn approximate_ignores_tiny_lengths() {
+		assert!(Length::approximate(0, TPB).is_empty());
+		assert!(Length::approximate(5, TPB).is_empty());
+	}
+
+	#[test]
+	fn split_uses_plain_and_dotted_values() {
+		assert_eq!(names(&Length::split(96 * 4, TPB)), ["whole"]);
+		assert_eq!(
+			names(&Length::split(96 * 7, TPB)),
+			["dotted whole", "quarter"]
+		);
+		assert_eq!(
+			names(&Length::split(96 * 5 / 4, TPB)),
+			["quarter", "sixteenth"]
+		);
+		assert!(Length::split(2, TPB).is_empty());
+	}
+
+	#[test]
+	fn beats() {
+		let four = TimeSignature::default();
+		let beat = |offset| Beat::new(offset, TPB, four).to_string();
+		assert_eq!(beat(0), "beat 1");
+		assert_eq!(beat(96), "beat 2");
+		assert_eq!(beat(48), "beat 1 and");
+		assert_eq!(beat(24), "beat 1e");
+		assert_eq!(beat(72), "beat 1a");
+		assert_eq!(beat(32), "beat 1 trip");
+		assert_eq!(beat(64), "beat 1 let");
+		// Close enough to the next beat to be on it.
+		assert_eq!(beat(95), "beat 2");
+	}
+
+	#[test]
+	fn beats_follow_the_denominator() {
+		let six_eight = TimeSignature {
+			numerator: 6,
+			denominator: 3,
+		};
+		assert_eq!(Beat::new(48, TPB, six_eight).to_string(), "beat 2");
+		assert_eq!(Beat::new(240, TPB, six_eight).to_string(), "beat 6");
+	}
+}
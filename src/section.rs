@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// A named place in the song: a marker or cue point in the file, or a bookmark.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Section {
+	/// The bar the section starts at.
+	pub bar: usize,
+	pub name: String,
+	/// Whether the user added it, as opposed to the file.
+	#[serde(skip)]
+	pub bookmark: bool,
+}
+
+/// Returns the names of the sections starting at the `n`th bar.
+pub fn names_at(sections: &[Section], n: usize) -> Vec<String> {
+	sections
+		.iter()
+		.filter(|s| s.bar == n)
+		.map(|s| s.name.clone())
+		.collect()
+}
+
+/// Returns the first section after the `n`th bar.
+pub fn next(sections: &[Section], n: usize) -> Option<&Section> {
+	sections.iter().find(|s| s.bar > n)
+}
+
+/// Returns the last section before the `n`th bar.
+pub fn prev(sections: &[Section], n: usize) -> Option<&Section> {
+	sections.iter().rev().find(|s| s.bar < n)
+}
+
+/// Finds a section by the start of its name, ignoring case.
+pub fn find<'a>(sections: &'a [Section], name: &str) -> Option<&'a Section> {
+	let name = name.trim().to_lowercase();
+	sections
+		.iter()
+		.find(|s| s.name.to_lowercase() == name)
+		.or_else(|| {
+			sections
+				.iter()
+				.find(|s| s.name.to_lowercase().starts_with(&name))
+		})
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{init::Selection, note::NoteStyle};
+
+/// Where a file was left off, restored the next time it is opened.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+	/// The last bar played.
+	pub bar: usize,
+	pub transposition: i8,
+	pub speed: f32,
+	pub solo: bool,
+	/// The part of the song that was followed.
+	pub selection: Selection,
+	pub note_style: NoteStyle,
+}
@@ -0,0 +1,297 @@
+use std::{fmt, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::note::Note;
+
+static TAB: RwLock<Option<Tab>> = RwLock::new(None);
+
+/// How many partial fingerings are tried at most for a group of notes.
+const SEARCH_BUDGET: u32 = 20_000;
+
+/// Sets the instrument used by the tablature note style.
+pub fn set(tab: Tab) {
+	*TAB.write().unwrap() = Some(tab);
+}
+
+fn get() -> Tab {
+	TAB.read().unwrap().clone().unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Tuning {
+	/// E A D G B E
+	Standard,
+	/// D A D G B E
+	DropD,
+	/// D G D G B D
+	OpenG,
+	/// E A D G
+	Bass,
+	/// B E A D G
+	Bass5,
+	/// MIDI keys of the open strings, from the lowest string to the highest.
+	Custom(Vec<u8>),
+}
+
+impl Tuning {
+	/// Returns the open strings, from the lowest to the highest.
+	fn strings(&self) -> &[u8] {
+		match self {
+			Self::Standard => &[40, 45, 50, 55, 59, 64],
+			Self::DropD => &[38, 45, 50, 55, 59, 64],
+			Self::OpenG => &[38, 43, 50, 55, 59, 62],
+			Self::Bass => &[28, 33, 38, 43],
+			Self::Bass5 => &[23, 28, 33, 38, 43],
+			Self::Custom(strings) => strings,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Tab {
+	pub tuning: Tuning,
+	/// The fret the capo is on, 0 for no capo. Frets are counted from the capo.
+	pub capo: u8,
+	/// The highest fret on the instrument.
+	pub frets: u8,
+}
+
+impl Default for Tab {
+	fn default() -> Self {
+		Self {
+			tuning: Tuning::Standard,
+			capo: 0,
+			frets: 24,
+		}
+	}
+}
+
+/// A place on the fretboard.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Position {
+	/// Strings are numbered from the highest, starting at 1.
+	pub string: u8,
+	pub fret: u8,
+}
+
+impl fmt::Display for Position {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.fret == 0 {
+			write!(f, "string {} open", self.string)
+		} else {
+			write!(f, "string {} fret {}", self.string, self.fret)
+		}
+	}
+}
+
+/// Why a note was left out of a fingering.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Unplaced {
+	/// The note is too low or too high for the instrument.
+	OutOfRange,
+	/// Every string it fits on is taken by another note.
+	NoFreeString,
+}
+
+impl fmt::Display for Unplaced {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::OutOfRange => "out of range",
+			Self::NoFreeString => "no free string",
+		})
+	}
+}
+
+impl Tab {
+	/// Returns every position `pitch` can be played at, as (string index from
+	/// the lowest, fret).
+	fn positions(&self, pitch: u8) -> Vec<(usize, u8)> {
+		let highest = self.frets.saturating_sub(self.capo);
+		self.tuning
+			.strings()
+			.iter()
+			.enumerate()
+			.filter_map(|(i, &open)| {
+				let open = open.saturating_add(self.capo);
+				pitch
+					.checked_sub(open)
+					.filter(|&fret| fret <= highest)
+					.map(|fret| (i, fret))
+			})
+			.collect()
+	}
+
+	/// Finds the easiest way to play `notes` together, one note per string.
+	///
+	/// Notes that are left out say why.
+	fn fingering(&self, notes: &[Note]) -> Vec<Result<Position, Unplaced>> {
+		let n_strings = self.tuning.strings().len();
+		let options = notes
+			.iter()
+			.map(|n| self.positions(n.pitch()))
+			.collect::<Vec<_>>();
+
+		// No more notes than there are strings can be placed. Rather than trying
+		// every way of leaving the rest out, keep the bass and the highest notes.
+		let mut kept = (0..notes.len())
+			.filter(|&i| !options[i].is_empty())
+			.collect::<Vec<_>>();
+		if kept.len() > n_strings {
+			kept.sort_by_key(|&i| std::cmp::Reverse(notes[i].pitch()));
+			let bass = kept.pop();
+			kept.truncate(n_strings.saturating_sub(1));
+			if n_strings > 0 {
+				kept.extend(bass);
+			}
+			kept.sort_unstable();
+		}
+		let kept_options = kept.iter().map(|&i| options[i].clone()).collect::<Vec<_>>();
+
+		// Start from a quick guess so the search can rule out worse attempts early.
+		let guess = greedy(&kept_options);
+		let mut best = (cost(&guess), guess);
+		let mut current = vec![None; kept.len()];
+		let mut budget = SEARCH_BUDGET;
+		self.search(&kept_options, 0, 0, &mut current, &mut best, &mut budget);
+
+		let mut buf = options
+			.iter()
+			.map(|o| {
+				if o.is_empty() {
+					Err(Unplaced::OutOfRange)
+				} else {
+					Err(Unplaced::NoFreeString)
+				}
+			})
+			.collect::<Vec<_>>();
+		for (&i, p) in kept.iter().zip(best.1) {
+			if let Some((string, fret)) = p {
+				buf[i] = Ok(Position {
+					string: (n_strings - string) as u8,
+					fret,
+				});
+			}
+		}
+		buf
+	}
+
+	fn search(
+		&self,
+		options: &[Vec<(usize, u8)>],
+		i: usize,
+		used: u32,
+		current: &mut [Option<(usize, u8)>],
+		best: &mut (u32, Vec<Option<(usize, u8)>>),
+		budget: &mut u32,
+	) {
+		// Instruments with many strings can have too many ways to try them all;
+		// settle for the best one found so far.
+		if *budget == 0 {
+			return;
+		}
+		*budget -= 1;
+		// Adding notes never lowers the cost, so give up on worse attempts early.
+		let cost = cost(&current[..i]);
+		if cost >= best.0 {
+			return;
+		}
+		if i == options.len() {
+			*best = (cost, current.to_vec());
+			return;
+		}
+
+		for &(string, fret) in &options[i] {
+			if used & 1 << string == 0 {
+				current[i] = Some((string, fret));
+				self.search(options, i + 1, used | 1 << string, current, best, budget);
+			}
+		}
+		// Leave the note out if nothing else fits.
+		current[i] = None;
+		self.search(options, i + 1, used, current, best, budget);
+	}
+}
+
+/// Finds the easiest way to play `notes` together on the configured instrument.
+pub fn fingering(notes: &[Note]) -> Vec<Result<Position, Unplaced>> {
+	get().fingering(notes)
+}
+
+/// Places every note on the free string with the lowest fret, in order.
+fn greedy(options: &[Vec<(usize, u8)>]) -> Vec<Option<(usize, u8)>> {
+	let mut used = 0_u32;
+	options
+		.iter()
+		.map(|opts| {
+			let p = opts
+				.iter()
+				.filter(|&&(string, _)| used & 1 << string == 0)
+				.min_by_key(|&&(_, fret)| fret)
+				.copied();
+			if let Some((string, _)) = p {
+				used |= 1 << string;
+			}
+			p
+		})
+		.collect()
+}
+
+/// Scores a fingering; lower is easier. Dropped notes cost the most, then a
+/// wide stretch, then playing high up the neck.
+fn cost(fingering: &[Option<(usize, u8)>]) -> u32 {
+	let dropped = fingering.iter().filter(|p| p.is_none()).count() as u32;
+	let fretted = fingering
+		.iter()
+		.flatten()
+		.map(|&(_, fret)| fret as u32)
+		.filter(|&f| f > 0);
+	let (min, max, sum) = fretted.fold((u32::MAX, 0, 0), |(min, max, sum), f| {
+		(min.min(f), max.max(f), sum + f)
+	});
+	let span = max.saturating_sub(min);
+
+	dropped * 10_000 + span * 100 + sum
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn notes(pitches: &[u8]) -> Vec<Note> {
+		pitches.iter().map(|&p| Note::from(p)).collect()
+	}
+
+	#[test]
+	fn open_chord() {
+		let f = Tab::default().fingering(&notes(&[48, 52, 55, 60, 64]));
+		let f = f
+			.iter()
+			.map(|p| p.map(|p| (p.string, p.fret)))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			f,
+			[Ok((5, 3)), Ok((4, 2)), Ok((3, 0)), Ok((2, 1)), Ok((1, 0))]
+		);
+	}
+
+	#[test]
+	fn dense_chords_keep_the_bass_and_the_top() {
+		let pitches = (40..60).collect::<Vec<u8>>();
+		let f = Tab::default().fingering(&notes(&pitches));
+		assert!(f.iter().flatten().count() <= 6);
+		assert!(f[0].is_ok());
+		assert!(f[19].is_ok());
+		assert!(f.iter().all(|p| *p != Err(Unplaced::OutOfRange)));
+		assert!(f.contains(&Err(Unplaced::NoFreeString)));
+	}
+
+	#[test]
+	fn notes_off_the_neck_are_out_of_range() {
+		let f = Tab::default().fingering(&notes(&[30, 52, 100]));
+		assert_eq!(f[0], Err(Unplaced::OutOfRange));
+		assert!(f[1].is_ok());
+		assert_eq!(f[2], Err(Unplaced::OutOfRange));
+	}
+}
@@ -0,0 +1,134 @@
+use std::{fmt, str::FromStr};
+
+use crate::{key::Key, names};
+
+/// Transposing instruments, their other names and how many semitones above
+/// the sounding pitch their parts are written.
+const INSTRUMENTS: [(&str, &[&str], i8); 14] = [
+	("concert pitch", &["concert", "c instrument"], 0),
+	("B♭ clarinet", &["clarinet", "bb clarinet"], 2),
+	("A clarinet", &[], 3),
+	("E♭ clarinet", &["eb clarinet"], -3),
+	("bass clarinet", &["bb bass clarinet"], 14),
+	("B♭ trumpet", &["trumpet", "bb trumpet"], 2),
+	("F horn", &["horn", "french horn"], 7),
+	(
+		"E♭ alto sax",
+		&["alto sax", "alto saxophone", "eb alto sax"],
+		9,
+	),
+	(
+		"B♭ tenor sax",
+		&["tenor sax", "tenor saxophone", "bb tenor sax"],
+		14,
+	),
+	(
+		"B♭ soprano sax",
+		&["soprano sax", "soprano saxophone", "bb soprano sax"],
+		2,
+	),
+	(
+		"E♭ baritone sax",
+		&["baritone sax", "bari sax", "eb baritone sax"],
+		21,
+	),
+	("guitar", &[], 12),
+	("bass guitar", &["bass"], 12),
+	("piccolo", &[], -12),
+];
+
+/// What to transpose to.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Target {
+	Key(Key),
+	/// An index into `INSTRUMENTS`.
+	Instrument(usize),
+}
+
+impl Target {
+	/// Returns the transposition that reaches `self` from a piece in `key`.
+	///
+	/// Keys are reached by the shortest way. A transposition cannot change the
+	/// mode, so a key in the other mode than the piece is an error.
+	pub fn transposition(self, key: Option<Key>) -> Result<i8, &'static str> {
+		match self {
+			Self::Instrument(i) => Ok(INSTRUMENTS[i].2),
+			Self::Key(target) => {
+				let key = key.ok_or("there is no key signature to transpose from")?;
+				match (key.minor, target.minor) {
+					(false, true) => return Err("the piece is in a major key"),
+					(true, false) => return Err("the piece is in a minor key"),
+					_ => (),
+				}
+				let n = (target.tonic() as i32 - key.tonic() as i32).rem_euclid(12) as i8;
+				Ok(if n > 6 { n - 12 } else { n })
+			}
+		}
+	}
+}
+
+impl FromStr for Target {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let name = s
+			.trim()
+			.to_lowercase()
+			.replace('♭', "b")
+			.split_whitespace()
+			.collect::<Vec<_>>()
+			.join(" ");
+		let instrument = INSTRUMENTS.iter().position(|(full, others, _)| {
+			full.to_lowercase().replace('♭', "b") == name || others.contains(&name.as_str())
+		});
+		match instrument {
+			Some(i) => Ok(Self::Instrument(i)),
+			None => s.parse::<Key>().map(Self::Key).map_err(|_| {
+				"expected a key such as 'G major' or an instrument such as 'alto sax'"
+			}),
+		}
+	}
+}
+
+impl fmt::Display for Target {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Key(k) => write!(f, "to {}", k),
+			Self::Instrument(i) => write!(f, "for {}", names::symbols(INSTRUMENTS[*i].0)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn transposition(target: &str, key: &str) -> Result<i8, &'static str> {
+		let target = target.parse::<Target>().unwrap();
+		target.transposition(Some(key.parse().unwrap()))
+	}
+
+	#[test]
+	fn keys_are_reached_the_shortest_way() {
+		assert_eq!(transposition("G major", "C major"), Ok(-5));
+		assert_eq!(transposition("D major", "C major"), Ok(2));
+		assert_eq!(transposition("E minor", "A minor"), Ok(-5));
+		assert_eq!(transposition("C minor", "A minor"), Ok(3));
+	}
+
+	#[test]
+	fn the_mode_has_to_match() {
+		assert!(transposition("G minor", "C major").is_err());
+		assert!(transposition("G major", "E minor").is_err());
+	}
+
+	#[test]
+	fn instruments() {
+		assert_eq!(transposition("clarinet", "C major"), Ok(2));
+		assert!("G minor"
+			.parse::<Target>()
+			.unwrap()
+			.transposition(None)
+			.is_err());
+	}
+}